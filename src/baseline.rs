@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::config::BaselineConfig;
+use crate::lighthouse::{normalize_url, parse_report_file, same_url, AuditMap};
+use crate::metrics::LighthouseMetrics;
+use crate::summary::{
+    deltas_since, diff_metrics, load_summary_entries, move_aside_corrupt, same_env, summary_path, write_atomically, MetricDelta,
+    ENV_SEPARATOR,
+};
+
+const BASELINE_FILE: &str = "baseline.json";
+
+/// A recorded automatic move of the baseline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BaselineShift {
+    pub from: f64,
+    pub to: f64,
+    pub at: String,
+}
+
+/// The moving performance-score baseline for one scenario and URL.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MovingBaseline {
+    pub performance_score: f64,
+    /// Consecutive runs that beat the baseline by more than the noise threshold.
+    pub streak: u32,
+    /// Sum of the scores in the current streak, used to average the confirmed level.
+    pub streak_total: f64,
+    pub shifts: Vec<BaselineShift>,
+}
+
+/// What the moving baseline made of the latest run.
+#[derive(Debug, Clone)]
+pub enum BaselineOutcome {
+    /// No baseline existed yet; one was derived from history.
+    Initialized(f64),
+    /// The run is within the noise threshold of the baseline.
+    Steady(f64),
+    /// The run improved, but not yet for enough consecutive runs.
+    Improving { baseline: f64, streak: u32 },
+    /// The improvement was confirmed and the baseline moved.
+    Shifted(BaselineShift),
+    /// The run scored below the baseline by more than the noise threshold.
+    Regressed { baseline: f64, score: f64 },
+}
+
+/// A fixed comparison point loaded from `--baseline-file`, used instead of each
+/// scenario's previous `summary.json` entry.
+#[derive(Debug, Clone)]
+pub enum PinnedBaseline {
+    /// Per-scenario entries, from a saved `--format json` result or a `summary.json`.
+    Entries(Vec<Value>),
+    /// Metrics (in seconds) from a single Lighthouse report, compared against every scenario.
+    Report(LighthouseMetrics),
+}
+
+impl PinnedBaseline {
+    /// Loads a `--format json` result, a `summary.json`, or a Lighthouse JSON report.
+    pub fn load(path: &Path, max_bytes: u64, audits: &AuditMap) -> Result<Self, Box<dyn Error>> {
+        let mut json = parse_report_file(path, max_bytes)?;
+        if json.get("audits").is_some() {
+            return Ok(PinnedBaseline::Report(LighthouseMetrics::from_json_with(&json, audits)?.to_seconds()));
+        }
+        match json.get_mut("scenarios").map(Value::take).unwrap_or(json) {
+            Value::Array(entries) => Ok(PinnedBaseline::Entries(entries)),
+            _ => Err(format!(
+                "{} is not a Lighthouse report, a --format json result or a summary.json",
+                path.display()
+            )
+            .into()),
+        }
+    }
+
+    /// Compares `metrics` against the baseline for the same scenario, URL and environment,
+    /// or `None` when the baseline has no entry for them.
+    pub fn compare(&self, scenario: &str, url: &str, env: Option<&str>, metrics: &LighthouseMetrics) -> Option<Vec<MetricDelta>> {
+        match self {
+            PinnedBaseline::Report(baseline) => Some(diff_metrics(baseline, metrics)),
+            PinnedBaseline::Entries(entries) => {
+                let entry = entries.iter().rev().find(|e| {
+                    e["scenario"].as_str() == Some(scenario)
+                        && same_url(e["url"].as_str().unwrap_or_default(), url)
+                        && same_env(e, env)
+                })?;
+                Some(deltas_since(entry["metrics"].as_object()?, metrics))
+            }
+        }
+    }
+}
+
+fn key(scenario: &str, url: &str, env: Option<&str>) -> String {
+    match env {
+        Some(env) => format!("{}{}{}|{}", scenario, ENV_SEPARATOR, env, normalize_url(url)),
+        None => format!("{}|{}", scenario, normalize_url(url)),
+    }
+}
+
+/// Reads `baseline.json`. A file that doesn't parse is moved aside to
+/// `<name>.corrupt-<timestamp>` and reported as an error, so the recorded baselines
+/// are neither silently reset nor overwritten.
+fn load_baselines(path: &Path) -> io::Result<HashMap<String, MovingBaseline>> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str(&content).or_else(|e| {
+        let backup = move_aside_corrupt(path)?;
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} could not be parsed ({}); moved it to {}", path.display(), e, backup.display()),
+        ))
+    })
+}
+
+/// Mean performance score of the `summary.json` history for a scenario, URL and environment.
+fn historical_score(out_dir: &Path, scenario: &str, url: &str, env: Option<&str>) -> io::Result<Option<f64>> {
+    let entries = load_summary_entries(&summary_path(out_dir))?;
+    let scores: Vec<f64> = entries
+        .iter()
+        .filter(|e| {
+            e["scenario"].as_str() == Some(scenario) && same_url(e["url"].as_str().unwrap_or_default(), url) && same_env(e, env)
+        })
+        .filter_map(|e| e["metrics"]["performance_score"].as_f64())
+        .collect();
+
+    if scores.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(scores.iter().sum::<f64>() / scores.len() as f64))
+    }
+}
+
+/// Feeds a run's performance score into the moving baseline in `out_dir/baseline.json`
+/// and persists the result.
+///
+/// The baseline only moves after `confirmations` consecutive runs beat it by more
+/// than `noise_threshold`; it then moves `weight` of the way towards the average
+/// of those runs. Any run that doesn't beat the threshold resets the streak.
+pub fn update_moving_baseline(
+    out_dir: &Path,
+    scenario: &str,
+    url: &str,
+    env: Option<&str>,
+    score: f64,
+    settings: &BaselineConfig,
+) -> io::Result<BaselineOutcome> {
+    let path = out_dir.join(BASELINE_FILE);
+    let mut baselines = load_baselines(&path)?;
+
+    let outcome = match baselines.get_mut(&key(scenario, url, env)) {
+        None => {
+            let initial = historical_score(out_dir, scenario, url, env)?.unwrap_or(score);
+            baselines.insert(
+                key(scenario, url, env),
+                MovingBaseline { performance_score: initial, ..Default::default() },
+            );
+            BaselineOutcome::Initialized(initial)
+        }
+        Some(baseline) if score > baseline.performance_score + settings.noise_threshold => {
+            baseline.streak += 1;
+            baseline.streak_total += score;
+
+            if baseline.streak >= settings.confirmations {
+                let confirmed = baseline.streak_total / baseline.streak as f64;
+                let from = baseline.performance_score;
+                let to = from + settings.weight * (confirmed - from);
+                let shift = BaselineShift { from, to, at: Utc::now().to_rfc3339() };
+
+                baseline.performance_score = to;
+                baseline.streak = 0;
+                baseline.streak_total = 0.0;
+                baseline.shifts.push(shift.clone());
+                BaselineOutcome::Shifted(shift)
+            } else {
+                BaselineOutcome::Improving { baseline: baseline.performance_score, streak: baseline.streak }
+            }
+        }
+        Some(baseline) => {
+            baseline.streak = 0;
+            baseline.streak_total = 0.0;
+            if score < baseline.performance_score - settings.noise_threshold {
+                BaselineOutcome::Regressed { baseline: baseline.performance_score, score }
+            } else {
+                BaselineOutcome::Steady(baseline.performance_score)
+            }
+        }
+    };
+
+    write_atomically(&path, serde_json::to_string_pretty(&baselines)?.as_bytes())?;
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn corrupt_baselines_are_reported_and_kept() {
+        let out_dir = TempDir::new("baseline");
+        let corrupt = r#"{"baseline|https://example.com/": {"performance_score": 9"#;
+        fs::write(out_dir.join(BASELINE_FILE), corrupt).unwrap();
+
+        let result = update_moving_baseline(&out_dir, "baseline", "https://example.com", None, 90.0, &BaselineConfig::default());
+        let backups: Vec<String> = fs::read_dir(&out_dir)
+            .unwrap()
+            .map(|e| fs::read_to_string(e.unwrap().path()).unwrap())
+            .collect();
+        let baseline_exists = out_dir.join(BASELINE_FILE).exists();
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(backups, [corrupt]);
+        assert!(!baseline_exists);
+    }
+}
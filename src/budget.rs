@@ -0,0 +1,237 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::LighthouseMetrics;
+
+/// Performance limits a scenario must stay within. Unset limits are not checked.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Budget {
+    pub min_performance_score: Option<f64>,
+    pub max_lcp_seconds: Option<f64>,
+    pub max_tbt_seconds: Option<f64>,
+    /// Maximum transfer size in bytes per resource type (`Script`, `Image`, ...), or `total`.
+    pub bytes: BTreeMap<String, f64>,
+}
+
+/// A budget file: top-level limits apply to every scenario, and
+/// `[scenarios.<label>]` tables override individual limits per scenario.
+/// Byte budgets go in a `[bytes]` table (or `[scenarios.<label>.bytes]`).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Budgets {
+    #[serde(flatten)]
+    pub default: Budget,
+    pub scenarios: HashMap<String, Budget>,
+}
+
+/// A single metric that breached its budget.
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetViolation {
+    pub metric: String,
+    pub actual: f64,
+    pub limit: f64,
+}
+
+/// How much of a resource type's byte budget a run used.
+#[derive(Debug, Clone, Serialize)]
+pub struct ByteUsage {
+    pub resource_type: String,
+    pub bytes: f64,
+    pub limit: f64,
+}
+
+impl ByteUsage {
+    /// Transfer size as a percentage of the budget.
+    pub fn utilization(&self) -> f64 {
+        if self.limit > 0.0 {
+            self.bytes / self.limit * 100.0
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    /// Whether the budget was exceeded; a transfer size that couldn't be measured counts as over.
+    pub fn is_over(&self) -> bool {
+        self.bytes.is_nan() || self.bytes > self.limit
+    }
+
+    /// The overage as a violation, if the budget was exceeded.
+    pub fn violation(&self) -> Option<BudgetViolation> {
+        self.is_over().then(|| BudgetViolation {
+            metric: format!("{} bytes", self.resource_type),
+            actual: self.bytes,
+            limit: self.limit,
+        })
+    }
+}
+
+impl BudgetViolation {
+    /// How far past the limit the metric landed, always positive.
+    pub fn overage(&self) -> f64 {
+        (self.actual - self.limit).abs()
+    }
+}
+
+impl fmt::Display for BudgetViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.actual.is_finite() {
+            write!(f, "{}: no usable value (the audit errored or was not run)", self.metric)
+        } else if self.metric == "Performance Score" {
+            write!(
+                f,
+                "{}: {:.1} is below the minimum {:.1} by {:.1}",
+                self.metric, self.actual, self.limit, self.overage()
+            )
+        } else if self.metric.ends_with(" bytes") {
+            write!(
+                f,
+                "{}: {:.1} KiB exceeds the maximum {:.1} KiB by {:.1} KiB",
+                self.metric,
+                self.actual / 1024.0,
+                self.limit / 1024.0,
+                self.overage() / 1024.0
+            )
+        } else {
+            write!(
+                f,
+                "{}: {:.2}s exceeds the maximum {:.2}s by {:.2}s",
+                self.metric, self.actual, self.limit, self.overage()
+            )
+        }
+    }
+}
+
+impl Budgets {
+    /// Loads a budget file from disk.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let raw = fs::read_to_string(path)?;
+        Ok(toml::from_str(&raw)?)
+    }
+
+    /// Returns the effective budget for `scenario`, applying any overrides.
+    pub fn for_scenario(&self, scenario: &str) -> Budget {
+        let mut budget = self.default.clone();
+        if let Some(overrides) = self.scenarios.get(scenario) {
+            budget.min_performance_score = overrides.min_performance_score.or(budget.min_performance_score);
+            budget.max_lcp_seconds = overrides.max_lcp_seconds.or(budget.max_lcp_seconds);
+            budget.max_tbt_seconds = overrides.max_tbt_seconds.or(budget.max_tbt_seconds);
+            budget.bytes.extend(overrides.bytes.iter().map(|(kind, limit)| (kind.clone(), *limit)));
+        }
+        budget
+    }
+}
+
+impl Budget {
+    /// Checks metrics (already converted with `to_seconds`) against this budget.
+    /// A budgeted metric that isn't finite, e.g. NaN from an errored audit, is a violation.
+    pub fn check(&self, metrics: &LighthouseMetrics) -> Vec<BudgetViolation> {
+        let mut violations = Vec::new();
+
+        if let Some(min) = self.min_performance_score {
+            if !metrics.performance_score.is_finite() || metrics.performance_score < min {
+                violations.push(BudgetViolation { metric: "Performance Score".into(), actual: metrics.performance_score, limit: min });
+            }
+        }
+        if let Some(max) = self.max_lcp_seconds {
+            if !metrics.largest_contentful_paint.is_finite() || metrics.largest_contentful_paint > max {
+                violations.push(BudgetViolation { metric: "LCP".into(), actual: metrics.largest_contentful_paint, limit: max });
+            }
+        }
+        if let Some(max) = self.max_tbt_seconds {
+            if !metrics.total_blocking_time.is_finite() || metrics.total_blocking_time > max {
+                violations.push(BudgetViolation { metric: "TBT".into(), actual: metrics.total_blocking_time, limit: max });
+            }
+        }
+
+        violations
+    }
+
+    /// Compares a run's transfer size per resource type (see `extract_byte_breakdown`)
+    /// against the byte budgets. Resource types match case-insensitively; `total` covers
+    /// every request. An empty breakdown means the report had no request data, so every
+    /// byte budget gets NaN bytes, which counts as over, rather than passing on 0.
+    pub fn byte_usage(&self, breakdown: &[(String, f64)]) -> Vec<ByteUsage> {
+        self.bytes
+            .iter()
+            .map(|(resource_type, limit)| {
+                if breakdown.is_empty() {
+                    return ByteUsage { resource_type: resource_type.clone(), bytes: f64::NAN, limit: *limit };
+                }
+                let bytes = breakdown
+                    .iter()
+                    .filter(|(kind, _)| resource_type.eq_ignore_ascii_case("total") || kind.eq_ignore_ascii_case(resource_type))
+                    .map(|(_, bytes)| bytes)
+                    .sum();
+                ByteUsage { resource_type: resource_type.clone(), bytes, limit: *limit }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUDGETS: &str = r#"
+        min_performance_score = 90
+        max_lcp_seconds = 2.5
+
+        [bytes]
+        Script = 102400
+        total = 512000
+
+        [scenarios.checkout]
+        max_lcp_seconds = 4.0
+
+        [scenarios.checkout.bytes]
+        Script = 204800
+    "#;
+
+    fn metrics(score: f64, lcp: f64) -> LighthouseMetrics {
+        LighthouseMetrics { performance_score: score, largest_contentful_paint: lcp, ..Default::default() }
+    }
+
+    #[test]
+    fn scenario_overrides_replace_only_the_limits_they_set() {
+        let budgets: Budgets = toml::from_str(BUDGETS).unwrap();
+        let checkout = budgets.for_scenario("checkout");
+        let other = budgets.for_scenario("home");
+
+        assert_eq!(checkout.min_performance_score, Some(90.0));
+        assert_eq!(checkout.max_lcp_seconds, Some(4.0));
+        assert_eq!(checkout.bytes["Script"], 204800.0);
+        assert_eq!(checkout.bytes["total"], 512000.0);
+        assert!(checkout.check(&metrics(95.0, 3.0)).is_empty());
+        assert_eq!(other.check(&metrics(95.0, 3.0))[0].metric, "LCP");
+    }
+
+    #[test]
+    fn byte_budgets_match_resource_types_and_total() {
+        let budgets: Budgets = toml::from_str(BUDGETS).unwrap();
+        let breakdown = [("script".to_string(), 150_000.0), ("Image".to_string(), 400_000.0)];
+
+        let usage = budgets.for_scenario("home").byte_usage(&breakdown);
+        let violations: Vec<String> = usage.iter().filter_map(ByteUsage::violation).map(|v| v.metric).collect();
+
+        assert_eq!(usage.iter().map(|u| u.bytes).collect::<Vec<_>>(), [150_000.0, 550_000.0]);
+        assert_eq!(violations, ["Script bytes", "total bytes"]);
+        assert!(budgets.for_scenario("checkout").byte_usage(&breakdown)[0].violation().is_none());
+    }
+
+    #[test]
+    fn nan_metrics_violate_their_budgets() {
+        let budgets: Budgets = toml::from_str(BUDGETS).unwrap();
+        let violations = budgets.for_scenario("home").check(&metrics(f64::NAN, f64::NAN));
+        let without_requests = budgets.for_scenario("home").byte_usage(&[]);
+
+        assert_eq!(violations.iter().map(|v| v.metric.as_str()).collect::<Vec<_>>(), ["Performance Score", "LCP"]);
+        assert!(violations[0].to_string().contains("no usable value"));
+        assert!(without_requests.iter().all(|usage| usage.bytes.is_nan() && usage.is_over()));
+        assert_eq!(without_requests.len(), 2);
+    }
+}
@@ -0,0 +1,351 @@
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+
+use crate::lighthouse::CacheState;
+use crate::metrics::Aggregation;
+use crate::upload::S3Target;
+
+/// Command-line options for the performance tracker.
+#[derive(Debug, Parser)]
+#[command(name = "performance-tracker", about = "Runs Lighthouse scenarios and tracks performance metrics")]
+pub struct Args {
+    /// Path to a TOML config file; defaults apply when omitted.
+    #[arg(long)]
+    pub config: Option<String>,
+
+    /// Directory to write reports, logs and summary.json into (overrides `output.out_dir`).
+    #[arg(long, value_name = "DIR")]
+    pub out_dir: Option<PathBuf>,
+
+    /// Path to a TOML performance budget; the process exits non-zero if any scenario breaches it.
+    #[arg(long)]
+    pub budget: Option<String>,
+
+    /// Write a JUnit XML report to this path, one test case per scenario, failing on budget violations.
+    #[arg(long, value_name = "PATH")]
+    pub junit: Option<PathBuf>,
+
+    /// Export the full summary.json history as CSV to this path after all scenarios complete.
+    #[arg(long, value_name = "PATH")]
+    pub export_csv: Option<PathBuf>,
+
+    /// After the run, compare each scenario's score and LCP against the run nearest to this many days ago.
+    #[arg(long, value_name = "DAYS")]
+    pub compare_period: Option<i64>,
+
+    /// Also record each scenario run in this SQLite database.
+    #[arg(long, value_name = "PATH")]
+    pub db: Option<String>,
+
+    /// File of URL patterns (one per line, `#` comments allowed) blocked in every scenario.
+    #[arg(long, value_name = "FILE")]
+    pub denylist: Option<PathBuf>,
+
+    /// TOML file remapping metrics to Lighthouse audit IDs (`first_cpu_idle = "interactive"`),
+    /// for Lighthouse releases that rename or drop audits.
+    #[arg(long, value_name = "FILE")]
+    pub audit_map: Option<PathBuf>,
+
+    /// Warn when a report was produced by a different Lighthouse version.
+    #[arg(long, value_name = "VERSION")]
+    pub require_lh_version: Option<String>,
+
+    /// Lighthouse runs per scenario and cache pass.
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    pub runs: u32,
+
+    /// Scenario cache passes to run at the same time. Concurrent runs compete for CPU and
+    /// network and read slower, so keep this at 1 when comparing against earlier results.
+    #[arg(long, default_value_t = 1, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub concurrency: u32,
+
+    /// How the runs of each scenario are combined.
+    #[arg(long, value_enum, default_value_t = Aggregation::Mean)]
+    pub aggregation: Aggregation,
+
+    /// Abort on malformed blocked URL patterns instead of warning.
+    #[arg(long)]
+    pub strict_patterns: bool,
+
+    /// Environment name, e.g. `staging`, added to report file names and summary.json entries
+    /// so runs against different environments keep separate histories and summaries.
+    #[arg(long, value_name = "LABEL", value_parser = parse_env_label)]
+    pub env_label: Option<String>,
+
+    /// Only log errors; the final summary table is still printed.
+    #[arg(long, short)]
+    pub quiet: bool,
+
+    /// `json` prints the whole run as one JSON object on stdout; `ndjson` prints each scenario
+    /// result as a JSON line as soon as it completes. Both move progress output to stderr.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// JSON object of extra HTTP headers to send, e.g. '{"Authorization": "Basic ..."}'.
+    #[arg(long, value_name = "JSON")]
+    pub extra_headers: Option<String>,
+
+    /// Extra flags passed through to Chrome, e.g. "--ignore-certificate-errors".
+    #[arg(long, value_name = "FLAGS", allow_hyphen_values = true)]
+    pub chrome_flags: Option<String>,
+
+    /// Send Chrome's and Lighthouse's traffic through this proxy, e.g. "http://proxy.corp:3128".
+    #[arg(long, value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// Also upload this run's reports and summaries to an S3-compatible bucket, e.g.
+    /// "perf-artifacts/ci/nightly". Needs a build with `--features s3`; credentials and
+    /// region come from the usual AWS_* environment variables.
+    #[arg(long, value_name = "BUCKET/PREFIX")]
+    pub s3: Option<S3Target>,
+
+    /// Exit non-zero if any scenario's performance score dropped by more than this many
+    /// points since its previous summary.json entry (or its `--baseline-file` entry).
+    /// Scenarios without one are skipped.
+    #[arg(long, value_name = "POINTS")]
+    pub fail_on_regression: Option<f64>,
+
+    /// strftime format of the timestamp in report and summary file names (overrides
+    /// `output.timestamp_format`); must start with %Y-%m-%d. Defaults to "%Y-%m-%dT%H-%M-%S".
+    #[arg(long, value_name = "FORMAT")]
+    pub timestamp_format: Option<String>,
+
+    /// Exit non-zero if fewer than this percentage of Lighthouse runs succeeded, so results
+    /// averaged from too few runs don't pass silently.
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percent)]
+    pub min_success_rate: Option<f64>,
+
+    /// Compare every scenario against this fixed baseline instead of its previous run: a saved
+    /// `--format json` result, a summary.json, or a single Lighthouse JSON report.
+    #[arg(long, value_name = "FILE")]
+    pub baseline_file: Option<PathBuf>,
+
+    /// Number of recent summary.json runs per scenario used for trend regression detection.
+    #[arg(long, default_value_t = 10, value_name = "N")]
+    pub trend_window: usize,
+
+    /// Flag a regression when the score is more than this many standard deviations below the recent mean.
+    #[arg(long, default_value_t = 2.0, value_name = "K")]
+    pub trend_sigmas: f64,
+
+    /// Lighthouse executable to run, e.g. ./node_modules/.bin/lighthouse.
+    #[arg(long, value_name = "PATH", default_value = "lighthouse")]
+    pub lighthouse_path: PathBuf,
+
+    /// File of URLs (one per line) to run every scenario against, instead of the default site.
+    #[arg(long, value_name = "FILE")]
+    pub urls_file: Option<PathBuf>,
+
+    /// Run only this scenario; repeat for several.
+    #[arg(long, value_name = "LABEL")]
+    pub only: Vec<String>,
+
+    /// Leave this scenario out; repeat for several.
+    #[arg(long, value_name = "LABEL")]
+    pub skip: Vec<String>,
+
+    /// Write each report's filmstrip screenshots to a `filmstrip_<label>_<date>` directory.
+    #[arg(long)]
+    pub save_filmstrip: bool,
+
+    /// Save JSON reports gzipped as `lighthouse_report_*.json.gz`; the summary reads either form.
+    #[arg(long)]
+    pub compress: bool,
+
+    /// Don't keep the `lighthouse_report_*.json` files; metrics still reach `summary.json`.
+    #[arg(long, conflicts_with = "compress")]
+    pub no_save_reports: bool,
+
+    /// Lighthouse config file (custom gatherers, audits, settings) forwarded as `--config-path`.
+    /// Our desktop preset and category list are then left out so the config's own settings apply.
+    #[arg(long, value_name = "FILE")]
+    pub lh_config: Option<PathBuf>,
+
+    /// After the run, compare the 75th-percentile LCP of two scenarios across all their runs,
+    /// with a bootstrap confidence interval, e.g. `--compare-p75 baseline no-tealium`.
+    #[arg(long, num_args = 2, value_names = ["BASE", "CANDIDATE"])]
+    pub compare_p75: Option<Vec<String>>,
+
+    /// Run the whole scenario suite this many times, then print each scenario's min, mean,
+    /// max and standard deviation across iterations.
+    #[arg(long, default_value_t = 1, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub repeat: u32,
+
+    /// Keep running the whole suite every `--interval` minutes until Ctrl-C, appending each
+    /// iteration to summary.json. Ctrl-C lets the scenario in flight finish; press it again
+    /// to quit at once.
+    #[arg(long, conflicts_with = "repeat")]
+    pub watch: bool,
+
+    /// Minutes between the starts of `--watch` iterations.
+    #[arg(long, default_value_t = 60, value_name = "MINUTES", requires = "watch", value_parser = clap::value_parser!(u64).range(1..))]
+    pub interval: u64,
+
+    /// Seconds to pause between `--repeat` iterations.
+    #[arg(long, default_value_t = 0, value_name = "SECONDS")]
+    pub cooldown: u64,
+
+    /// Before running any scenario, wait until this URL answers 200 OK, e.g. a dev server that's starting up.
+    #[arg(long, value_name = "URL")]
+    pub wait_for: Option<String>,
+
+    /// How long `--wait-for` keeps polling before giving up.
+    #[arg(long, default_value_t = 60, value_name = "SECONDS")]
+    pub wait_timeout: u64,
+
+    /// Kill a Lighthouse run (and its Chrome) after this many seconds and count it as failed.
+    #[arg(long, default_value_t = 120, value_name = "SECONDS")]
+    pub run_timeout: u64,
+
+    /// Keep parsed reports in this directory, so the summary and re-parse modes skip
+    /// reports that haven't changed since an earlier invocation.
+    #[arg(long, value_name = "DIR")]
+    pub report_cache: Option<PathBuf>,
+
+    /// Run this Lighthouse user-flow script with Node (once per URL, passed as its argument)
+    /// and report each step's metrics instead of running the scenarios.
+    #[arg(long, value_name = "SCRIPT")]
+    pub flow_script: Option<PathBuf>,
+
+    /// Summarize the main thread of each scenario from the trace Lighthouse saves with its
+    /// report, and include it in the report.
+    #[arg(long)]
+    pub sample_trace: bool,
+
+    /// Rebuild summary.json and the txt logs in this directory from its saved reports, without running Lighthouse.
+    #[arg(long, value_name = "DIR")]
+    pub reparse_dir: Option<PathBuf>,
+
+    /// Print per-scenario daily averages of every saved report from this date (YYYY-MM-DD) on, then exit.
+    #[arg(long, value_name = "DATE")]
+    pub since: Option<chrono::NaiveDate>,
+
+    /// Compare two saved JSON reports metric by metric and exit without running any scenario.
+    #[arg(long, num_args = 2, value_names = ["BEFORE", "AFTER"])]
+    pub diff: Option<Vec<PathBuf>>,
+
+    /// Compare the latest entry of each scenario in two summary.json files, e.g. from two
+    /// releases, print the changes and a verdict, and exit non-zero on a net regression.
+    #[arg(long, num_args = 2, value_names = ["OLD", "NEW"])]
+    pub compare_summaries: Option<Vec<PathBuf>>,
+
+    /// Print the Lighthouse command for each scenario without running it or writing any files.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Audit with an empty cache (first visit), a primed cache (repeat visit), or both.
+    #[arg(long, value_enum, default_value_t = CacheMode::Cold)]
+    pub cache_mode: CacheMode,
+
+    /// Measured warm-cache runs per scenario, after one priming run. Adds a warm pass
+    /// to `--cache-mode cold`; defaults to `--runs` when only `--cache-mode` asks for warm runs.
+    #[arg(long, value_name = "M", value_parser = clap::value_parser!(u32).range(1..))]
+    pub warm_runs: Option<u32>,
+
+    /// Number of times a failed Lighthouse run is retried before it counts as a failure.
+    #[arg(long, default_value_t = 3)]
+    pub max_retries: u32,
+
+    /// Base delay in milliseconds between retries; doubles after each attempt.
+    #[arg(long, default_value_t = 500)]
+    pub retry_base_delay_ms: u64,
+
+    /// CPU slowdown multiplier; overrides `throttling.cpu_slowdown_multiplier` in the config.
+    #[arg(long, value_name = "MULTIPLIER")]
+    pub cpu_slowdown: Option<f64>,
+
+    /// Network round-trip time in ms; overrides `throttling.rtt_ms` in the config.
+    #[arg(long, value_name = "MS")]
+    pub rtt_ms: Option<f64>,
+
+    /// Network throughput in kbps; overrides `throttling.throughput_kbps` in the config.
+    #[arg(long, value_name = "KBPS")]
+    pub throughput_kbps: Option<f64>,
+
+    /// Incoming webhook URL to post the scenario summary to after the run.
+    #[arg(long, value_name = "URL")]
+    pub webhook: Option<String>,
+
+    /// Payload shape for `--webhook`.
+    #[arg(long, value_enum, default_value_t = WebhookFormat::Slack)]
+    pub webhook_format: WebhookFormat,
+}
+
+/// Accepts environment labels that are safe in file names and can't be confused
+/// with the scenario/date separators.
+fn parse_env_label(label: &str) -> Result<String, String> {
+    if !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        Ok(label.to_string())
+    } else {
+        Err("use only letters, digits and '-'".to_string())
+    }
+}
+
+/// Accepts a percentage between 0 and 100.
+fn parse_percent(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent),
+        _ => Err("expected a percentage between 0 and 100".to_string()),
+    }
+}
+
+/// How the run's results are presented on stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    /// One compact JSON line per scenario result, printed as soon as it completes.
+    Ndjson,
+}
+
+impl OutputFormat {
+    /// Whether stdout carries machine-readable results, so status output must go to stderr.
+    pub fn is_machine_readable(self) -> bool {
+        self != OutputFormat::Text
+    }
+}
+
+/// Which chat service's JSON shape the webhook payload uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum WebhookFormat {
+    Slack,
+    Discord,
+}
+
+/// Which cache states each scenario is audited under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CacheMode {
+    Cold,
+    Warm,
+    Both,
+}
+
+impl Args {
+    /// The cache states to run per scenario, taking `--warm-runs` into account.
+    pub fn cache_passes(&self) -> Vec<CacheState> {
+        match (self.cache_mode, self.warm_runs) {
+            (CacheMode::Cold, Some(_)) => CacheMode::Both.passes(),
+            (mode, _) => mode.passes(),
+        }
+    }
+
+    /// How many measured runs a cache pass gets.
+    pub fn runs_for(&self, cache: CacheState) -> u32 {
+        match cache {
+            CacheState::Cold => self.runs,
+            CacheState::Warm => self.warm_runs.unwrap_or(self.runs),
+        }
+    }
+}
+
+impl CacheMode {
+    /// The cache states to run, in order.
+    pub fn passes(&self) -> Vec<CacheState> {
+        match self {
+            CacheMode::Cold => vec![CacheState::Cold],
+            CacheMode::Warm => vec![CacheState::Warm],
+            CacheMode::Both => vec![CacheState::Cold, CacheState::Warm],
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{LazyLock, RwLock};
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, TimeZone};
+use serde::Deserialize;
+
+use crate::lighthouse::DEFAULT_MAX_REPORT_BYTES;
+use crate::metrics::{CwvThresholds, Weights};
+
+/// Settings loaded from an optional TOML config file.
+///
+/// Every field has a default, so a missing file (or a file that only sets a
+/// few keys) behaves exactly like running without one.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub output: OutputConfig,
+    pub baseline: BaselineConfig,
+    pub limits: LimitsConfig,
+    pub throttling: Throttling,
+    /// Weights and thresholds for the composite score computed from raw metrics.
+    pub scoring: Weights,
+    /// Good / poor thresholds for the Core Web Vitals classification.
+    pub cwv: CwvThresholds,
+    /// Scenarios to run; the built-in set is used when none are configured.
+    pub scenarios: Vec<ScenarioConfig>,
+}
+
+/// A named set of URL patterns to block during a run.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ScenarioConfig {
+    pub label: String,
+    #[serde(default)]
+    pub blocked: Vec<String>,
+}
+
+impl ScenarioConfig {
+    fn new(label: &str, blocked: &[&str]) -> Self {
+        ScenarioConfig {
+            label: label.to_string(),
+            blocked: blocked.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+/// The scenarios run when the config file doesn't define any.
+pub fn builtin_scenarios() -> Vec<ScenarioConfig> {
+    vec![
+        ScenarioConfig::new("baseline", &[]),
+        ScenarioConfig::new("no-tealium", &["*.tealiumiq.com"]),
+        ScenarioConfig::new("no-appd", &["*.appdynamics.com"]),
+        ScenarioConfig::new("no-optimizely", &["*.optimizely.com"]),
+        ScenarioConfig::new("no-header-footer", &["*/header*", "*/footer*"]),
+        ScenarioConfig::new("no-quantum", &["*.quantummetric.com"]),
+    ]
+}
+
+/// Narrows `scenarios` to the labels in `only` (all when empty), minus those in `skip`.
+///
+/// Fails if any label in `only` or `skip` doesn't name a scenario, so typos don't
+/// silently run (or skip) the wrong thing.
+pub fn select_scenarios(
+    scenarios: Vec<ScenarioConfig>,
+    only: &[String],
+    skip: &[String],
+) -> Result<Vec<ScenarioConfig>, Box<dyn Error>> {
+    let unknown: Vec<&str> = only
+        .iter()
+        .chain(skip)
+        .filter(|label| !scenarios.iter().any(|s| &s.label == *label))
+        .map(String::as_str)
+        .collect();
+    check_unique_labels(&scenarios)?;
+    if !unknown.is_empty() {
+        let available: Vec<&str> = scenarios.iter().map(|s| s.label.as_str()).collect();
+        return Err(format!(
+            "unknown scenario(s): {}; available: {}",
+            unknown.join(", "),
+            available.join(", ")
+        )
+        .into());
+    }
+
+    Ok(scenarios
+        .into_iter()
+        .filter(|s| only.is_empty() || only.contains(&s.label))
+        .filter(|s| !skip.contains(&s.label))
+        .collect())
+}
+
+/// Fails if two scenarios share a label: their runs, reports and history would be mixed up.
+pub fn check_unique_labels(scenarios: &[ScenarioConfig]) -> Result<(), Box<dyn Error>> {
+    let mut seen = HashSet::new();
+    let duplicates: Vec<&str> = scenarios.iter().map(|s| s.label.as_str()).filter(|label| !seen.insert(*label)).collect();
+    if duplicates.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("duplicate scenario label(s): {}", duplicates.join(", ")).into())
+    }
+}
+
+/// Default `output.timestamp_format`: date and time, so same-day runs don't overwrite each other.
+pub const DEFAULT_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H-%M-%S";
+
+/// When the current run started; every file it names carries this time.
+static RUN_STARTED: LazyLock<RwLock<DateTime<Local>>> = LazyLock::new(|| RwLock::new(Local::now()));
+
+/// Starts a new run: files named from now on carry the current time. `--watch` and
+/// `--repeat` call this before each iteration so iterations don't overwrite each other's files.
+pub fn start_new_run() {
+    *RUN_STARTED.write().unwrap_or_else(|e| e.into_inner()) = Local::now();
+}
+
+/// The current run's start time in `format`, as used in report and summary file names.
+pub fn run_timestamp(format: &str) -> String {
+    RUN_STARTED.read().unwrap_or_else(|e| e.into_inner()).format(format).to_string()
+}
+
+/// Whether `format` tells apart runs started a minute apart, i.e. includes the time of day.
+pub fn timestamp_has_minutes(format: &str) -> bool {
+    let start = Local.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).single();
+    start.is_some_and(|start| start.format(format).to_string() != (start + chrono::Duration::minutes(1)).format(format).to_string())
+}
+
+/// Checks a file-name timestamp format. It must start with `%Y-%m-%d`, which is how saved
+/// reports are matched to their day, and must not produce the `_` and `@` that separate the
+/// parts of a report file name, a `.`, or a path separator.
+pub fn validate_timestamp_format(format: &str) -> Result<(), String> {
+    if !format.starts_with("%Y-%m-%d") {
+        return Err(format!("timestamp format \"{}\" must start with %Y-%m-%d", format));
+    }
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("timestamp format \"{}\" is not a valid strftime format", format));
+    }
+    if run_timestamp(format).contains(['_', '@', '.', '/', '\\']) {
+        return Err(format!("timestamp format \"{}\" must not produce '_', '@', '.', '/' or '\\'", format));
+    }
+    Ok(())
+}
+
+/// Per-artifact switches controlling which files a run produces, and where.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OutputConfig {
+    /// Directory every artifact is written to; created if missing.
+    pub out_dir: PathBuf,
+    /// Append each scenario to `summary.json`.
+    pub write_json: bool,
+    /// Write the human-readable `metrics_log_<date>.txt`.
+    pub write_txt: bool,
+    /// Write the `summary_<timestamp>.md` scenario table.
+    pub write_markdown: bool,
+    /// Write the self-contained `summary_<timestamp>.html` report.
+    pub write_html: bool,
+    /// Export the metrics history to `summary.csv` (`--export-csv` picks another path).
+    pub write_csv: bool,
+    /// Write `lighthouse_<scenario>.prom` files for the node_exporter textfile collector.
+    pub write_prometheus: bool,
+    /// strftime format of the timestamp in report and summary file names; see
+    /// [`validate_timestamp_format`].
+    pub timestamp_format: String,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            out_dir: PathBuf::from("."),
+            write_json: true,
+            write_txt: true,
+            write_markdown: false,
+            write_html: true,
+            write_csv: false,
+            write_prometheus: false,
+            timestamp_format: DEFAULT_TIMESTAMP_FORMAT.to_string(),
+        }
+    }
+}
+
+impl OutputConfig {
+    /// The current run's start time in `timestamp_format`.
+    pub fn timestamp(&self) -> String {
+        run_timestamp(&self.timestamp_format)
+    }
+}
+
+/// Controls the automatically moving performance-score baseline.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BaselineConfig {
+    /// Track a moving baseline in `baseline.json` and shift it after confirmed improvements.
+    pub auto_update: bool,
+    /// Score changes within this many points are treated as noise.
+    pub noise_threshold: f64,
+    /// Consecutive improving runs required before the baseline moves.
+    pub confirmations: u32,
+    /// Fraction of the confirmed improvement the baseline moves by (0.0–1.0).
+    pub weight: f64,
+}
+
+impl Default for BaselineConfig {
+    fn default() -> Self {
+        BaselineConfig {
+            auto_update: false,
+            noise_threshold: 2.0,
+            confirmations: 3,
+            weight: 0.5,
+        }
+    }
+}
+
+/// Safety limits for long-running or unattended use.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Reports and traces larger than this are rejected rather than loaded into memory.
+    pub max_report_bytes: u64,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            max_report_bytes: DEFAULT_MAX_REPORT_BYTES,
+        }
+    }
+}
+
+/// Network and CPU throttling passed to Lighthouse.
+///
+/// Unset values are left out of the command so the preset's defaults apply.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Throttling {
+    /// CPU slowdown multiplier (1.0 means no slowdown).
+    pub cpu_slowdown_multiplier: Option<f64>,
+    /// Round-trip time in milliseconds.
+    pub rtt_ms: Option<f64>,
+    /// Download throughput in kilobits per second.
+    pub throughput_kbps: Option<f64>,
+}
+
+impl Throttling {
+    /// The `--throttling.*` Lighthouse flags for every value that is set.
+    pub fn flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        if let Some(multiplier) = self.cpu_slowdown_multiplier {
+            flags.push(format!("--throttling.cpuSlowdownMultiplier={}", multiplier));
+        }
+        if let Some(rtt) = self.rtt_ms {
+            flags.push(format!("--throttling.rttMs={}", rtt));
+        }
+        if let Some(throughput) = self.throughput_kbps {
+            flags.push(format!("--throttling.throughputKbps={}", throughput));
+        }
+        flags
+    }
+}
+
+impl Config {
+    /// Loads the config at `path`, or the defaults when no path is given.
+    pub fn load(path: Option<&str>) -> Result<Self, Box<dyn Error>> {
+        match path {
+            Some(path) => {
+                let raw = fs::read_to_string(path)?;
+                let config: Config = toml::from_str(&raw)?;
+                check_unique_labels(&config.scenarios)?;
+                Ok(config)
+            }
+            None => Ok(Config::default()),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::error::Error;
+use std::path::Path;
+
+use sqlite::{Connection, State};
+
+use crate::metrics::LighthouseMetrics;
+
+/// SQLite-backed history of scenario runs, one row per aggregated run.
+pub struct MetricsDb {
+    connection: Connection,
+}
+
+impl MetricsDb {
+    /// Opens (or creates) the database at `path` and ensures the `metrics` table exists.
+    ///
+    /// Metric columns are nullable: a metric whose audit errored (NaN) is stored as NULL,
+    /// so it is never read back as a real zero.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let connection = Connection::open(path)?;
+        connection.execute(create_table("metrics"))?;
+
+        let mut existing = Vec::new();
+        let mut info = connection.prepare("PRAGMA table_info(metrics)")?;
+        while info.next()? == State::Row {
+            existing.push((info.read::<String>(1)?, info.read::<i64>(3)? != 0));
+        }
+        drop(info);
+
+        // Databases created before a metric field existed lack its column.
+        for field in LighthouseMetrics::FIELD_NAMES {
+            if !existing.iter().any(|(column, _)| column == field) {
+                connection.execute(format!("ALTER TABLE metrics ADD COLUMN {} REAL", field))?;
+            }
+        }
+
+        // Older databases declared the metric columns NOT NULL and stored errored audits
+        // as 0; SQLite can't drop the constraint in place, so the table is rebuilt.
+        let not_null = existing
+            .iter()
+            .any(|(column, not_null)| *not_null && LighthouseMetrics::FIELD_NAMES.contains(&column.as_str()));
+        if not_null {
+            let columns = format!("id, scenario, url, fetch_time, {}", LighthouseMetrics::FIELD_NAMES.join(", "));
+            connection.execute(format!(
+                "BEGIN;
+                DROP INDEX IF EXISTS metrics_scenario_time;
+                {}
+                INSERT INTO metrics_nullable ({columns}) SELECT {columns} FROM metrics;
+                DROP TABLE metrics;
+                ALTER TABLE metrics_nullable RENAME TO metrics;
+                COMMIT;",
+                create_table("metrics_nullable"),
+                columns = columns
+            ))?;
+        }
+        connection.execute("CREATE INDEX IF NOT EXISTS metrics_scenario_time ON metrics (scenario, fetch_time);")?;
+
+        Ok(MetricsDb { connection })
+    }
+
+    /// Inserts one row for a scenario run.
+    pub fn insert_run(
+        &self,
+        scenario: &str,
+        url: &str,
+        fetch_time: &str,
+        metrics: &LighthouseMetrics,
+    ) -> Result<(), Box<dyn Error>> {
+        let placeholders = vec!["?"; LighthouseMetrics::FIELD_NAMES.len()].join(", ");
+        let mut statement = self.connection.prepare(format!(
+            "INSERT INTO metrics (scenario, url, fetch_time, {}) VALUES (?, ?, ?, {})",
+            LighthouseMetrics::FIELD_NAMES.join(", "),
+            placeholders
+        ))?;
+
+        statement.bind(1, scenario)?;
+        statement.bind(2, url)?;
+        statement.bind(3, fetch_time)?;
+
+        let values = metrics.as_map();
+        for (i, field) in LighthouseMetrics::FIELD_NAMES.iter().enumerate() {
+            statement.bind(i + 4, Some(values[field]).filter(|value| value.is_finite()))?;
+        }
+
+        while statement.next()? != State::Done {}
+        Ok(())
+    }
+
+    /// Returns up to `limit` of the most recent runs for `scenario`, newest first,
+    /// as `(fetch_time, metrics)` pairs. Metrics stored as NULL come back as NaN.
+    pub fn recent_runs(&self, scenario: &str, limit: usize) -> Result<Vec<(String, LighthouseMetrics)>, Box<dyn Error>> {
+        let mut statement = self.connection.prepare(format!(
+            "SELECT fetch_time, {} FROM metrics WHERE scenario = ? ORDER BY fetch_time DESC LIMIT ?",
+            LighthouseMetrics::FIELD_NAMES.join(", ")
+        ))?;
+        statement.bind(1, scenario)?;
+        statement.bind(2, limit as i64)?;
+
+        let mut runs = Vec::new();
+        while statement.next()? == State::Row {
+            let fetch_time = statement.read::<String>(0)?;
+
+            let metrics = LighthouseMetrics::try_from_fields(|field| {
+                let column = LighthouseMetrics::FIELD_NAMES.iter().position(|name| *name == field).unwrap_or_default() + 1;
+                Ok::<_, sqlite::Error>(statement.read::<Option<f64>>(column)?.unwrap_or(f64::NAN))
+            })?;
+            runs.push((fetch_time, metrics));
+        }
+
+        Ok(runs)
+    }
+}
+
+/// The `CREATE TABLE` statement for the metrics table under `name`.
+fn create_table(name: &str) -> String {
+    let columns: Vec<String> = LighthouseMetrics::FIELD_NAMES.iter().map(|field| format!("{} REAL", field)).collect();
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            scenario TEXT NOT NULL,
+            url TEXT NOT NULL,
+            fetch_time TEXT NOT NULL,
+            {}
+        );",
+        name,
+        columns.join(",\n            ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn errored_metrics_round_trip_as_nan() {
+        let dir = TempDir::new("db");
+        let db = MetricsDb::open(dir.join("metrics.db")).unwrap();
+        let metrics = LighthouseMetrics { performance_score: 88.0, largest_contentful_paint: f64::NAN, ..Default::default() };
+
+        db.insert_run("baseline", "https://example.com", "2024-01-01T00:00:00Z", &metrics).unwrap();
+        let runs = db.recent_runs("baseline", 10).unwrap();
+
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].1.performance_score, 88.0);
+        assert!(runs[0].1.largest_contentful_paint.is_nan());
+        assert_eq!(runs[0].1.first_contentful_paint, 0.0);
+    }
+
+    #[test]
+    fn not_null_tables_are_rebuilt_with_their_rows() {
+        let dir = TempDir::new("db");
+        let path = dir.join("metrics.db");
+        let connection = Connection::open(&path).unwrap();
+        connection
+            .execute(
+                "CREATE TABLE metrics (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT, scenario TEXT NOT NULL, url TEXT NOT NULL,
+                    fetch_time TEXT NOT NULL, performance_score REAL NOT NULL
+                );
+                INSERT INTO metrics (scenario, url, fetch_time, performance_score)
+                    VALUES ('baseline', 'https://example.com', '2024-01-01T00:00:00Z', 75);",
+            )
+            .unwrap();
+        drop(connection);
+
+        let db = MetricsDb::open(&path).unwrap();
+        db.insert_run("baseline", "https://example.com", "2024-01-02T00:00:00Z", &LighthouseMetrics {
+            performance_score: f64::NAN,
+            ..Default::default()
+        })
+        .unwrap();
+        let runs = db.recent_runs("baseline", 10).unwrap();
+
+        assert!(runs[0].1.performance_score.is_nan());
+        assert_eq!(runs[1].1.performance_score, 75.0);
+        assert!(runs[1].1.largest_contentful_paint.is_nan());
+    }
+}
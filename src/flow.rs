@@ -0,0 +1,174 @@
+use std::error::Error;
+use std::path::Path;
+use std::process::Stdio;
+
+use log::{debug, info};
+use serde_json::Value;
+use tokio::io::AsyncReadExt;
+
+use crate::config::run_timestamp;
+use crate::lighthouse::{
+    kill_process_tree, parse_report_file, set_proxy_env, slugify_url, AuditMap, LighthouseError, LighthouseOptions,
+};
+use crate::metrics::LighthouseMetrics;
+use crate::status;
+
+/// Runs user-flow scripts; Lighthouse's user-flow API is only available from Node.
+pub const NODE_PROGRAM: &str = "node";
+
+/// The name a flow script gave one of its steps (e.g. "Add to cart").
+pub type StepName = String;
+
+/// Runs `script` with Node, passing `url` as its only argument, and parses the
+/// `FlowResult` JSON it prints to stdout.
+///
+/// The script is expected to end with
+/// `console.log(JSON.stringify(await flow.createFlowResult()))`. Its stderr is passed
+/// through so the script's own logging stays visible. Stdout is streamed straight to
+/// `options.out_dir` and kept there as `lighthouse_flow_<script>_<url>_<timestamp>.json`,
+/// since a flow with screenshots can print tens of megabytes.
+pub async fn run_flow_script(
+    script: &Path,
+    url: &str,
+    options: &LighthouseOptions,
+) -> Result<Vec<(StepName, LighthouseMetrics)>, Box<dyn Error>> {
+    if options.dry_run {
+        info!("🔎 Dry run [flow]: {} {} {}", NODE_PROGRAM, script.display(), url);
+        return Ok(Vec::new());
+    }
+
+    let mut command = tokio::process::Command::new(NODE_PROGRAM);
+    command.arg(script).arg(url).stdout(Stdio::piped()).stderr(Stdio::inherit());
+    if let Some(proxy) = &options.proxy {
+        set_proxy_env(&mut command, proxy);
+    }
+    // Own process group, so a timeout can take Chrome down with the script.
+    #[cfg(unix)]
+    command.process_group(0);
+    let mut child = command.spawn().map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => format!("`{}` was not found; --flow-script needs Node.js", NODE_PROGRAM).into(),
+        _ => Box::<dyn Error>::from(e),
+    })?;
+
+    let script_name = script.file_stem().and_then(|s| s.to_str()).unwrap_or("flow");
+    let file_name = options.out_dir.join(format!(
+        "lighthouse_flow_{}_{}_{}.json",
+        script_name,
+        slugify_url(url),
+        run_timestamp(&options.timestamp_format)
+    ));
+    let partial = file_name.with_extension("json.part");
+    let discard = |e: Box<dyn Error>| {
+        let _ = std::fs::remove_file(&partial);
+        Err(e)
+    };
+
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let max_bytes = options.max_report_bytes;
+    let finished = tokio::time::timeout(options.run_timeout, async {
+        let mut file = tokio::fs::File::create(&partial).await?;
+        let written = tokio::io::copy(&mut (&mut stdout).take(max_bytes.saturating_add(1)), &mut file).await?;
+        file.sync_all().await?;
+        if written > max_bytes {
+            return Ok(Err(written));
+        }
+        Ok::<_, std::io::Error>(Ok(child.wait().await?))
+    })
+    .await;
+    let status = match finished {
+        Ok(Ok(Ok(status))) => status,
+        Ok(Ok(Err(size))) => {
+            kill_process_tree(&mut child).await;
+            let source = format!("output of {}", script.display());
+            return discard(LighthouseError::ReportTooLarge { source, size, limit: max_bytes }.into());
+        }
+        Ok(Err(e)) => {
+            kill_process_tree(&mut child).await;
+            return discard(e.into());
+        }
+        Err(_) => {
+            kill_process_tree(&mut child).await;
+            return discard(LighthouseError::TimedOut(options.run_timeout).into());
+        }
+    };
+    if !status.success() {
+        return discard(LighthouseError::Failed(status).into());
+    }
+
+    let json = match parse_report_file(&partial, max_bytes) {
+        Ok(json) => json,
+        Err(e) => return discard(format!("{} did not print a flow result: {}", script.display(), e).into()),
+    };
+    std::fs::rename(&partial, &file_name)?;
+    debug!("✅ Saved flow result: {}", file_name.display());
+
+    Ok(extract_flow_steps(&json, &options.audit_map)?)
+}
+
+/// Extracts each step's metrics from a Lighthouse `FlowResult`, in flow order.
+///
+/// Steps without a name are called "Step N". Timespan and snapshot steps don't
+/// measure page-load metrics such as FCP, so those fields read 0 for them.
+pub fn extract_flow_steps(json: &Value, audits: &AuditMap) -> Result<Vec<(StepName, LighthouseMetrics)>, LighthouseError> {
+    let steps = json["steps"]
+        .as_array()
+        .ok_or_else(|| LighthouseError::InvalidOutput("flow result has no `steps` array".to_string()))?;
+    steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            let name = step["name"].as_str().map_or_else(|| format!("Step {}", i + 1), str::to_string);
+            Ok((name, LighthouseMetrics::from_json_with(&step["lhr"], audits)?))
+        })
+        .collect()
+}
+
+/// Prints one line per step of a flow run against `url`.
+pub fn print_flow_steps(script: &Path, url: &str, steps: &[(StepName, LighthouseMetrics)]) {
+    status!("\n=== User Flow: {} ({}) ===", script.display(), url);
+    for (name, metrics) in steps {
+        let m = metrics.to_seconds();
+        let format = LighthouseMetrics::format_field;
+        status!(
+            "{:<24} | Perf: {:>5.1} | FCP: {:>6} | LCP: {:>6} | TBT: {:>6} | CLS: {:>5} | INP: {:>6}",
+            name,
+            m.performance_score,
+            format("first_contentful_paint", m.first_contentful_paint),
+            format("largest_contentful_paint", m.largest_contentful_paint),
+            format("total_blocking_time", m.total_blocking_time),
+            format("cumulative_layout_shift", m.cumulative_layout_shift),
+            format("interaction_to_next_paint", m.interaction_to_next_paint)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_keep_flow_order_and_names() {
+        let lhr = |lcp: f64| {
+            serde_json::json!({
+                "categories": { "performance": { "score": 0.5 } },
+                "audits": { "largest-contentful-paint": { "numericValue": lcp } },
+            })
+        };
+        let flow = serde_json::json!({
+            "name": "Checkout",
+            "steps": [
+                { "name": "Home", "lhr": lhr(1200.0) },
+                { "lhr": lhr(900.0) },
+            ],
+        });
+
+        let steps = extract_flow_steps(&flow, &AuditMap::default()).unwrap();
+
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].0, "Home");
+        assert_eq!(steps[0].1.largest_contentful_paint, 1200.0);
+        assert_eq!(steps[1].0, "Step 2");
+        assert_eq!(steps[1].1.largest_contentful_paint, 900.0);
+        assert!(extract_flow_steps(&serde_json::json!({}), &AuditMap::default()).is_err());
+    }
+}
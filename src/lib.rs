@@ -0,0 +1,25 @@
+pub mod baseline;
+pub mod budget;
+pub mod cli;
+pub mod config;
+pub mod db;
+pub mod flow;
+pub mod lighthouse;
+pub mod lighthouse_summary;
+pub mod metrics;
+pub mod output;
+pub mod postprocess;
+pub mod report;
+pub mod report_cache;
+pub mod run_report;
+pub mod runner;
+pub mod summary;
+#[cfg(test)]
+mod test_support;
+pub mod trace;
+pub mod upload;
+pub mod webhook;
+
+pub use metrics::LighthouseMetrics;
+pub use run_report::RunReport;
+pub use runner::{run_scenarios, run_scenarios_with, RunConfig};
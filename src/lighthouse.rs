@@ -1,11 +1,794 @@
+use std::collections::{BTreeSet, HashMap};
 use std::error::Error;
-use std::process::Command;
-use chrono::Local;
-use serde_json::Value;
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
-use serde_json::to_string_pretty;
-use crate::metrics::LighthouseMetrics;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::Duration;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use reqwest::Url;
+use crate::config::{run_timestamp, Throttling, DEFAULT_TIMESTAMP_FORMAT};
+use crate::metrics::{LighthouseMetrics, MetricsError};
+
+/// Default cap on report size; generous enough for reports produced with `--save-assets`.
+pub const DEFAULT_MAX_REPORT_BYTES: u64 = 256 * 1024 * 1024;
+
+/// How long a single Lighthouse run may take before it's killed.
+pub const DEFAULT_RUN_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The audit each [`LighthouseMetrics`] field is read from, for the current Lighthouse release.
+pub const DEFAULT_AUDITS: &[(&str, &str)] = &[
+    ("first_contentful_paint", "first-contentful-paint"),
+    ("largest_contentful_paint", "largest-contentful-paint"),
+    ("time_to_interactive", "interactive"),
+    ("total_blocking_time", "total-blocking-time"),
+    ("cumulative_layout_shift", "cumulative-layout-shift"),
+    ("speed_index", "speed-index"),
+    ("first_meaningful_paint", "first-meaningful-paint"),
+    ("first_cpu_idle", "first-cpu-idle"),
+    ("max_potential_fid", "max-potential-fid"),
+    ("estimated_input_latency", "estimated-input-latency"),
+    ("interaction_to_next_paint", "interaction-to-next-paint"),
+    ("server_response_time", "server-response-time"),
+    ("javascript_bootup_time", "bootup-time"),
+    ("total_byte_weight", "total-byte-weight"),
+    ("render_blocking_resources", "render-blocking-resources"),
+    ("unused_javascript", "unused-javascript"),
+    ("unused_css", "unused-css"),
+    ("dom_size", "dom-size"),
+    ("preconnect_origins", "preconnect-to-required-origins"),
+    ("properly_sized_images", "uses-responsive-images"),
+    ("efficiently_encoded_images", "uses-optimized-images"),
+    ("minimize_main_thread_work", "mainthread-work-breakdown"),
+    ("minimize_render_blocking_stylesheets", "uses-rel-preload"),
+    ("avoid_large_layout_shifts", "layout-shift-elements"),
+];
+
+/// Maps metric fields to the Lighthouse audit IDs they're read from.
+///
+/// Starts from [`DEFAULT_AUDITS`]; an audit map file remaps individual fields when a
+/// Lighthouse release renames or replaces an audit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditMap {
+    audits: HashMap<String, String>,
+}
+
+impl Default for AuditMap {
+    fn default() -> Self {
+        AuditMap {
+            audits: DEFAULT_AUDITS
+                .iter()
+                .map(|(field, id)| (field.to_string(), id.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl AuditMap {
+    /// Loads a TOML file of `field = "audit-id"` overrides on top of the defaults.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let overrides: HashMap<String, String> = toml::from_str(&fs::read_to_string(path)?)?;
+        let mut map = AuditMap::default();
+        for (field, id) in overrides {
+            if !map.audits.contains_key(&field) {
+                return Err(format!("{}: '{}' is not a metric that's read from an audit", path.display(), field).into());
+            }
+            map.audits.insert(field, id);
+        }
+        Ok(map)
+    }
+
+    /// The audit `field` is read from.
+    pub fn audit_id<'a>(&'a self, field: &'a str) -> &'a str {
+        self.audits.get(field).map(String::as_str).unwrap_or(field)
+    }
+
+}
+
+/// Failures from a Lighthouse invocation or from reading its report.
+#[derive(Debug)]
+pub enum LighthouseError {
+    /// The `lighthouse` process exited with a non-zero status.
+    Failed(ExitStatus),
+    /// Lighthouse exited cleanly but its stdout was not a valid JSON report.
+    InvalidOutput(String),
+    /// A report exceeded the configured size limit and was not parsed.
+    ReportTooLarge { source: String, size: u64, limit: u64 },
+    /// Lighthouse completed but reported a `runtimeError` (e.g. `NO_FCP`), so the metrics are meaningless.
+    RuntimeError { code: String, message: String },
+    /// One or more blocked URL patterns can't match anything, as `(pattern, reason)` pairs.
+    InvalidPatterns(Vec<(String, &'static str)>),
+    /// `--extra-headers` wasn't a JSON object of string header values.
+    InvalidHeaders(String),
+    /// `--proxy` isn't a proxy URL Chrome accepts.
+    InvalidProxy(String),
+    /// Lighthouse didn't finish within the per-run timeout and was killed.
+    TimedOut(Duration),
+    /// The Lighthouse executable couldn't be found; `search_path` is the `PATH` it was looked up in.
+    NotInstalled { program: PathBuf, search_path: String },
+    /// The report parsed but its metrics look like junk (see [`LighthouseMetrics::is_suspicious`]).
+    SuspiciousResult,
+    /// A file read as a saved report is JSON but not a Lighthouse report; `missing` is the field it lacks.
+    NotAReport { path: PathBuf, missing: &'static str },
+}
+
+impl LighthouseError {
+    /// Whether running Lighthouse again could help: the process failed or timed out, its
+    /// output was garbled, or the metrics looked implausible. Setup and I/O errors are final.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            LighthouseError::Failed(_)
+                | LighthouseError::InvalidOutput(_)
+                | LighthouseError::TimedOut(_)
+                | LighthouseError::SuspiciousResult
+        )
+    }
+}
+
+impl fmt::Display for LighthouseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LighthouseError::Failed(status) => write!(f, "Lighthouse command failed with status: {}", status),
+            LighthouseError::InvalidOutput(reason) => write!(f, "Lighthouse produced invalid output: {}", reason),
+            LighthouseError::ReportTooLarge { source, size, limit } => write!(
+                f,
+                "{} is {:.1} MiB, over the {:.1} MiB report limit; raise `limits.max_report_bytes` in the config if this is expected",
+                source,
+                *size as f64 / 1_048_576.0,
+                *limit as f64 / 1_048_576.0
+            ),
+            LighthouseError::RuntimeError { code, message } => write!(f, "Lighthouse runtime error {}: {}", code, message),
+            LighthouseError::InvalidPatterns(invalid) => {
+                let listed: Vec<String> = invalid
+                    .iter()
+                    .map(|(pattern, reason)| format!("`{}` ({})", pattern, reason))
+                    .collect();
+                write!(f, "invalid blocked URL patterns: {}", listed.join(", "))
+            }
+            LighthouseError::InvalidHeaders(reason) => write!(f, "invalid extra headers: {}", reason),
+            LighthouseError::InvalidProxy(reason) => write!(f, "invalid proxy: {}", reason),
+            LighthouseError::TimedOut(limit) => {
+                write!(f, "Lighthouse did not finish within {}s and was killed", limit.as_secs())
+            }
+            LighthouseError::NotInstalled { program, search_path } => write!(
+                f,
+                "`{}` was not found. Install it with `npm i -g lighthouse` or point --lighthouse-path at a local install (PATH: {})",
+                program.display(),
+                search_path
+            ),
+            LighthouseError::NotAReport { path, missing } => {
+                write!(f, "{} is not a Lighthouse report (it has no `{}`)", path.display(), missing)
+            }
+            LighthouseError::SuspiciousResult => {
+                write!(f, "Lighthouse reported a performance score of 0 with no first paint; discarding the run")
+            }
+        }
+    }
+}
+
+impl Error for LighthouseError {}
+
+/// A runtime error keeps its own variant; a report without the expected structure is
+/// treated as garbled output.
+impl From<MetricsError> for LighthouseError {
+    fn from(e: MetricsError) -> Self {
+        match e {
+            MetricsError::RuntimeError { code, message } => LighthouseError::RuntimeError { code, message },
+            MetricsError::MissingSection(_) => LighthouseError::InvalidOutput(e.to_string()),
+        }
+    }
+}
+
+/// Whether the browser cache was empty (first visit) or primed (repeat visit).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CacheState {
+    #[default]
+    Cold,
+    Warm,
+}
+
+impl CacheState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheState::Cold => "cold",
+            CacheState::Warm => "warm",
+        }
+    }
+}
+
+/// Per-invocation settings for a Lighthouse run beyond the scenario itself.
+#[derive(Debug, Clone)]
+pub struct LighthouseOptions {
+    pub cache: CacheState,
+    /// Reports larger than this many bytes are rejected instead of parsed.
+    pub max_report_bytes: u64,
+    /// Print the assembled Lighthouse command instead of running it.
+    pub dry_run: bool,
+    /// Directory the JSON report is saved into.
+    pub out_dir: PathBuf,
+    /// Shared patterns blocked in every scenario, on top of its own list.
+    pub denylist: Vec<String>,
+    pub throttling: Throttling,
+    /// JSON object of extra HTTP headers sent with every request, e.g. for basic auth.
+    pub extra_headers: Option<String>,
+    /// Extra flags passed through to Chrome, space-separated.
+    pub chrome_flags: Option<String>,
+    /// Proxy URL that Chrome and the Lighthouse process send their traffic through.
+    pub proxy: Option<String>,
+    /// The Lighthouse executable to run.
+    pub lighthouse_path: PathBuf,
+    /// Also write the filmstrip frames as image files next to the report.
+    pub save_filmstrip: bool,
+    /// A run taking longer than this is killed, along with its Chrome processes.
+    pub run_timeout: Duration,
+    /// Save the JSON report gzipped, as `.json.gz`.
+    pub compress: bool,
+    /// Keep the JSON report once its metrics are extracted.
+    pub save_report: bool,
+    /// A Lighthouse config file passed as `--config-path`; it replaces our preset and categories.
+    pub lh_config: Option<PathBuf>,
+    /// Which audit each metric is read from.
+    pub audit_map: AuditMap,
+    /// strftime format of the timestamp in report file names.
+    pub timestamp_format: String,
+    /// Chrome profile to run in. A warm pass points every run at one [`WarmProfile`], so
+    /// the HTTP cache primed by its first run persists; `None` uses a throwaway profile.
+    pub profile_dir: Option<PathBuf>,
+}
+
+impl Default for LighthouseOptions {
+    fn default() -> Self {
+        LighthouseOptions {
+            cache: CacheState::default(),
+            max_report_bytes: DEFAULT_MAX_REPORT_BYTES,
+            dry_run: false,
+            out_dir: PathBuf::from("."),
+            denylist: Vec::new(),
+            throttling: Throttling::default(),
+            extra_headers: None,
+            chrome_flags: None,
+            proxy: None,
+            lighthouse_path: PathBuf::from("lighthouse"),
+            save_filmstrip: false,
+            run_timeout: DEFAULT_RUN_TIMEOUT,
+            compress: false,
+            save_report: true,
+            lh_config: None,
+            profile_dir: None,
+            audit_map: AuditMap::default(),
+            timestamp_format: DEFAULT_TIMESTAMP_FORMAT.to_string(),
+        }
+    }
+}
+
+/// Turns a "not found" spawn error into [`LighthouseError::NotInstalled`].
+fn spawn_error(program: &Path, e: std::io::Error) -> Box<dyn Error> {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        LighthouseError::NotInstalled {
+            program: program.to_path_buf(),
+            search_path: std::env::var("PATH").unwrap_or_default(),
+        }
+        .into()
+    } else {
+        e.into()
+    }
+}
+
+/// Kills a timed-out Lighthouse and everything it started, then reaps it.
+pub(crate) async fn kill_process_tree(child: &mut tokio::process::Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // The child leads its own process group (see `process_group(0)`), so this reaches Chrome too.
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+        }
+    }
+    let _ = child.kill().await;
+}
+
+/// Checks once, before any scenario runs, that Lighthouse can be started, returning its version.
+pub fn preflight(program: &Path) -> Result<String, Box<dyn Error>> {
+    let output = Command::new(program)
+        .arg("--version")
+        .output()
+        .map_err(|e| spawn_error(program, e))?;
+    if !output.status.success() {
+        return Err(LighthouseError::Failed(output.status).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// How often [`wait_for_url`] polls, and how long each attempt may take.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Polls `url` until it answers `200 OK`, e.g. for a dev server that's still starting.
+/// Gives up after `timeout`, reporting the last response or connection error.
+pub async fn wait_for_url(url: &str, timeout: Duration) -> Result<(), Box<dyn Error>> {
+    let client = reqwest::Client::builder().timeout(WAIT_POLL_INTERVAL * 5).build()?;
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let last = match client.get(url).send().await {
+            Ok(response) if response.status() == reqwest::StatusCode::OK => return Ok(()),
+            Ok(response) => format!("HTTP {}", response.status()),
+            Err(e) => e.to_string(),
+        };
+        if tokio::time::Instant::now() >= deadline {
+            return Err(format!("{} wasn't ready after {}s (last attempt: {})", url, timeout.as_secs(), last).into());
+        }
+        debug!("Waiting for {}: {}", url, last);
+        tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+    }
+}
+
+/// Checks that `raw` is a JSON object whose values are all strings, as Lighthouse's
+/// `--extra-headers` expects.
+pub fn validate_extra_headers(raw: &str) -> Result<(), LighthouseError> {
+    let json: Value = serde_json::from_str(raw).map_err(|e| LighthouseError::InvalidHeaders(e.to_string()))?;
+    let headers = json
+        .as_object()
+        .ok_or_else(|| LighthouseError::InvalidHeaders("expected a JSON object of header names to values".into()))?;
+    match headers.iter().find(|(_, value)| !value.is_string()) {
+        Some((name, _)) => Err(LighthouseError::InvalidHeaders(format!("value for `{}` is not a string", name))),
+        None => Ok(()),
+    }
+}
+
+/// Checks that `raw` is a proxy URL Chrome's `--proxy-server` accepts:
+/// `scheme://host[:port]` with an http, https, socks4 or socks5 scheme.
+pub fn validate_proxy(raw: &str) -> Result<(), LighthouseError> {
+    let invalid = |reason: &str| LighthouseError::InvalidProxy(format!("`{}` {}", raw, reason));
+    let url = Url::parse(raw).map_err(|e| invalid(&format!("is not a URL ({})", e)))?;
+    if !["http", "https", "socks4", "socks5"].contains(&url.scheme()) {
+        return Err(invalid("must use http, https, socks4 or socks5"));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err(invalid("has no host"));
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err(invalid("contains credentials, which Chrome's --proxy-server doesn't support"));
+    }
+    if (url.path() != "/" && !url.path().is_empty()) || url.query().is_some() {
+        return Err(invalid("must not have a path or query"));
+    }
+    Ok(())
+}
+
+/// Sets the standard proxy variables for a child process, for anything it fetches outside Chrome.
+pub(crate) fn set_proxy_env(command: &mut tokio::process::Command, proxy: &str) {
+    for var in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+        command.env(var, proxy);
+    }
+}
+
+/// Reads a denylist file: one URL pattern per line, blank lines and `#` comments ignored.
+pub fn load_denylist(path: &Path) -> std::io::Result<Vec<String>> {
+    let content = fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Explains why `pattern` isn't a usable `--blocked-url-patterns` glob, or `None` if it is.
+///
+/// Patterns must start with `*` or an `http(s)://` scheme, contain only URL characters,
+/// and have a host part made of letters, digits, `.`, `-`, `:` and `*`.
+fn pattern_problem(pattern: &str) -> Option<&'static str> {
+    if pattern.is_empty() {
+        return Some("empty pattern");
+    }
+    let rest = match pattern.strip_prefix("https://").or_else(|| pattern.strip_prefix("http://")) {
+        Some(rest) => rest,
+        None if pattern.starts_with('*') => pattern,
+        None => return Some("must start with `*` or http(s)://"),
+    };
+    if !pattern.chars().all(|c| c.is_ascii_alphanumeric() || "-._~:/?#[]@!$&'()*+,;=%".contains(c)) {
+        return Some("contains characters that can't appear in a URL");
+    }
+    let host = rest.split('/').next().unwrap_or_default();
+    if !host.chars().all(|c| c.is_ascii_alphanumeric() || ".-:*".contains(c)) {
+        return Some("host part contains characters that can't appear in a hostname");
+    }
+    if host.contains("..") || host.ends_with('.') {
+        return Some("host part has an empty label");
+    }
+    None
+}
+
+/// Checks every blocked URL pattern before Lighthouse is run, listing all the bad ones at once.
+pub fn validate_blocked_patterns(patterns: &[&str]) -> Result<(), LighthouseError> {
+    let invalid: Vec<(String, &'static str)> = patterns
+        .iter()
+        .filter_map(|pattern| pattern_problem(pattern).map(|reason| (pattern.to_string(), reason)))
+        .collect();
+    if invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(LighthouseError::InvalidPatterns(invalid))
+    }
+}
+
+/// Replaces `${VAR}` references in `text` with the variable's value from the environment
+/// (which includes `.env` once `dotenv` has run). `${VAR:-default}` falls back to `default`
+/// when `VAR` is unset or empty; an unset variable without a default is an error.
+pub fn expand_env_vars(text: &str) -> Result<String, String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find('}').ok_or_else(|| format!("unterminated `${{` in `{}`", text))?;
+        let (name, default) = match after[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&after[..end], None),
+        };
+        match (std::env::var(name).ok().filter(|value| !value.is_empty()), default) {
+            (Some(value), _) => expanded.push_str(&value),
+            (None, Some(default)) => expanded.push_str(default),
+            (None, None) => return Err(format!("environment variable `{}` is not set", name)),
+        }
+        rest = &after[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Reads a URL list file: one absolute http(s) URL per line, blank lines and lines
+/// starting with `#` ignored. (Inline `#` is kept since URLs can contain fragments.)
+/// `${VAR}` references are expanded with [`expand_env_vars`] before the URL is checked.
+pub fn load_url_list(path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let content = fs::read_to_string(path)?;
+    let mut urls = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = expand_env_vars(line).map_err(|e| format!("{}:{}: {}", path.display(), i + 1, e))?;
+        match reqwest::Url::parse(&line) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => urls.push(line),
+            _ => return Err(format!("{}:{}: `{}` is not an http(s) URL", path.display(), i + 1, line).into()),
+        }
+    }
+    Ok(urls)
+}
+
+/// Canonical form of a URL for storing and comparing history: lowercase scheme and host,
+/// no default port, no fragment and no trailing slash, so `https://Example.com:443/`
+/// and `https://example.com` are the same page. Unparseable input is returned trimmed.
+pub fn normalize_url(url: &str) -> String {
+    let Ok(parsed) = Url::parse(url.trim()) else {
+        return url.trim().to_string();
+    };
+    let mut normalized = format!("{}://{}", parsed.scheme(), parsed.host_str().unwrap_or_default());
+    // `port()` is already `None` when the port is the scheme's default.
+    if let Some(port) = parsed.port() {
+        normalized.push_str(&format!(":{}", port));
+    }
+    normalized.push_str(parsed.path().trim_end_matches('/'));
+    if let Some(query) = parsed.query() {
+        normalized.push('?');
+        normalized.push_str(query);
+    }
+    normalized
+}
+
+/// Whether two URLs are the same page once normalized with [`normalize_url`].
+pub fn same_url(a: &str, b: &str) -> bool {
+    normalize_url(a) == normalize_url(b)
+}
+
+/// Turns a URL into a lowercase, filesystem-safe slug for report file names, e.g.
+/// `https://example.com/Products?id=7` becomes `example-com-products-id-7`.
+pub fn slugify_url(url: &str) -> String {
+    const MAX_SLUG_LEN: usize = 100;
+
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let mut slug = String::new();
+    for c in without_scheme.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.truncate(MAX_SLUG_LEN);
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        String::from("root")
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Combines a scenario's blocked patterns with the shared denylist, dropping duplicates
+/// while keeping the first occurrence's order.
+pub fn merge_blocked_patterns<'a>(blocked_patterns: &'a [String], denylist: &'a [String]) -> Vec<&'a str> {
+    let mut merged: Vec<&str> = Vec::new();
+    for pattern in blocked_patterns.iter().chain(denylist).map(String::as_str) {
+        if !merged.contains(&pattern) {
+            merged.push(pattern);
+        }
+    }
+    merged
+}
+
+fn is_gzipped(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+/// Reads a saved report or trace, refusing files larger than `max_bytes` before loading them.
+///
+/// Files ending in `.gz` are decompressed; for those the limit applies to the decompressed size.
+/// Invalid UTF-8 is replaced with U+FFFD, with a warning, rather than failing the read.
+pub fn read_report_file(path: &Path, max_bytes: u64) -> Result<String, Box<dyn Error>> {
+    let too_large = |size| LighthouseError::ReportTooLarge {
+        source: path.display().to_string(),
+        size,
+        limit: max_bytes,
+    };
+
+    let size = fs::metadata(path)?.len();
+    if size > max_bytes {
+        return Err(too_large(size).into());
+    }
+    let contents = if is_gzipped(path) {
+        let mut contents = Vec::new();
+        GzDecoder::new(fs::File::open(path)?)
+            .take(max_bytes.saturating_add(1))
+            .read_to_end(&mut contents)?;
+        if contents.len() as u64 > max_bytes {
+            return Err(too_large(contents.len() as u64).into());
+        }
+        contents
+    } else {
+        fs::read(path)?
+    };
+    Ok(String::from_utf8(contents).unwrap_or_else(|e| {
+        warn!("⚠️ {} is not valid UTF-8; replacing the invalid bytes", path.display());
+        String::from_utf8_lossy(e.as_bytes()).into_owned()
+    }))
+}
+
+/// Parses a saved report or trace as JSON, streaming it from disk instead of holding
+/// the whole text in memory next to the parsed value.
+///
+/// Applies the same size limit and `.gz` handling as [`read_report_file`]. Only when
+/// streaming fails is the file read whole, so that invalid UTF-8 gets the same lossy
+/// treatment and a genuine syntax error is reported as such.
+pub fn parse_report_file(path: &Path, max_bytes: u64) -> Result<Value, Box<dyn Error>> {
+    let size = fs::metadata(path)?.len();
+    if size > max_bytes {
+        return Err(LighthouseError::ReportTooLarge { source: path.display().to_string(), size, limit: max_bytes }.into());
+    }
+    let file = fs::File::open(path)?;
+    let mut reader: std::io::Take<Box<dyn Read>> = if is_gzipped(path) {
+        (Box::new(GzDecoder::new(file)) as Box<dyn Read>).take(max_bytes.saturating_add(1))
+    } else {
+        (Box::new(file) as Box<dyn Read>).take(u64::MAX)
+    };
+    match serde_json::from_reader(std::io::BufReader::new(&mut reader)) {
+        // Anything left of the limit means the decompressed report fit within it.
+        Ok(json) if reader.limit() > 0 => Ok(json),
+        Err(e) if e.is_io() => Err(e.into()),
+        // Over the limit (reported by `read_report_file`), invalid UTF-8, or malformed JSON.
+        _ => serde_json::from_str(&read_report_file(path, max_bytes)?)
+            .map_err(|e| LighthouseError::InvalidOutput(format!("{}: {}", path.display(), e)).into()),
+    }
+}
+
+/// Reads a saved Lighthouse report, plain or gzipped, and checks that it is one: it must
+/// have a `lighthouseVersion` and an `audits` object. Parsing is as in [`parse_report_file`].
+pub fn load_report(path: &Path, max_bytes: u64) -> Result<Value, Box<dyn Error>> {
+    let json = parse_report_file(path, max_bytes)?;
+    let missing = if !json["lighthouseVersion"].is_string() {
+        Some("lighthouseVersion")
+    } else if !json["audits"].is_object() {
+        Some("audits")
+    } else {
+        None
+    };
+    match missing {
+        Some(missing) => Err(LighthouseError::NotAReport { path: path.to_path_buf(), missing }.into()),
+        None => Ok(json),
+    }
+}
+
+/// Pretty-prints `json` into `path`, gzipped if `compress`, without building the text in memory.
+fn write_report_json(path: &Path, json: &Value, compress: bool) -> std::io::Result<()> {
+    let mut writer = std::io::BufWriter::new(fs::File::create(path)?);
+    if compress {
+        let mut encoder = GzEncoder::new(writer, Compression::default());
+        serde_json::to_writer_pretty(&mut encoder, json)?;
+        writer = encoder.finish()?;
+    } else {
+        serde_json::to_writer_pretty(&mut writer, json)?;
+    }
+    writer.into_inner().map_err(|e| e.into_error())?.sync_all()
+}
+
+/// Loads a saved Lighthouse JSON report (see [`load_report`]) and parses its metrics.
+pub fn load_report_metrics(path: &Path, max_bytes: u64, audits: &AuditMap) -> Result<LighthouseMetrics, Box<dyn Error>> {
+    let json = load_report(path, max_bytes)?;
+    Ok(LighthouseMetrics::from_json_with(&json, audits)?)
+}
+
+/// A fresh Chrome profile for one warm-cache pass, so its runs share an HTTP cache that
+/// no earlier pass, day or concurrent invocation has touched. Removed when dropped.
+#[derive(Debug)]
+pub struct WarmProfile(PathBuf);
+
+impl WarmProfile {
+    /// Creates an empty `perf-tracker-profile-<label>-<uuid>` directory under the system temp dir.
+    pub fn create(label: &str) -> std::io::Result<Self> {
+        let dir = std::env::temp_dir().join(format!("perf-tracker-profile-{}-{}", label, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+        Ok(WarmProfile(dir))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for WarmProfile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.0) {
+            warn!("⚠️ Could not remove the warm Chrome profile {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+/// The outcome of a single Lighthouse audit.
+#[derive(Debug, Clone, Default)]
+pub struct LighthouseRun {
+    pub metrics: LighthouseMetrics,
+    /// The URL Lighthouse actually audited after following redirects.
+    pub final_url: String,
+    /// Time spent in redirects before reaching `final_url`, in milliseconds.
+    pub redirect_ms: f64,
+    /// Distinct hosts the page made requests to.
+    pub origins: BTreeSet<String>,
+    /// Cache state the audit ran with.
+    pub cache: CacheState,
+    /// Opportunity audits with their estimated savings, largest time savings first.
+    pub opportunities: Vec<Opportunity>,
+    pub metadata: RunMetadata,
+    /// Screenshot thumbnails of the load, in timing order; empty if the report has none.
+    pub filmstrip: Vec<FilmstripFrame>,
+    /// Transfer bytes per resource type, largest first; see [`extract_byte_breakdown`].
+    pub byte_breakdown: Vec<(String, f64)>,
+    /// The render-blocking request chains; see [`extract_critical_chains`].
+    pub critical_chains: CriticalChains,
+    /// Third-party cost per entity, most main-thread time first; see [`extract_third_parties`].
+    pub third_parties: Vec<ThirdParty>,
+    /// `(url, wasted_bytes, total_bytes)` per script, most waste first; see [`extract_unused_javascript`].
+    pub unused_javascript: Vec<(String, f64, f64)>,
+    /// Element count, depth and widest parent; see [`extract_dom_stats`].
+    pub dom: DomStats,
+    /// The trace `--save-assets` wrote for this run, if there is one.
+    pub trace_path: Option<PathBuf>,
+}
+
+/// One request in a critical request chain, with the requests it triggered.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChainRequest {
+    pub url: String,
+    /// Milliseconds since navigation start.
+    pub start_ms: f64,
+    pub end_ms: f64,
+    pub transfer_size: f64,
+    pub children: Vec<ChainRequest>,
+}
+
+/// The `critical-request-chains` audit as a tree, with its deepest and slowest paths.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CriticalChains {
+    pub roots: Vec<ChainRequest>,
+    /// Requests in the deepest chain.
+    pub max_depth: usize,
+    /// URLs along the chain that takes longest from its first request's start to its last one's end.
+    pub longest_path: Vec<String>,
+    pub longest_duration_ms: f64,
+}
+
+impl CriticalChains {
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+}
+
+/// One `screenshot-thumbnails` frame.
+#[derive(Debug, Clone, Serialize)]
+pub struct FilmstripFrame {
+    /// Milliseconds since navigation start.
+    pub timing_ms: f64,
+    /// The frame as a `data:image/...;base64,` URI, ready to embed in HTML.
+    pub data_uri: String,
+}
+
+impl FilmstripFrame {
+    /// File extension and decoded image bytes, or `None` if the URI isn't base64 image data.
+    pub fn decode(&self) -> Option<(&str, Vec<u8>)> {
+        let (header, data) = self.data_uri.strip_prefix("data:image/")?.split_once(";base64,")?;
+        let extension = if header == "jpeg" { "jpg" } else { header };
+        Some((extension, BASE64.decode(data).ok()?))
+    }
+}
+
+/// Which Lighthouse and Chrome produced a report, so results stay comparable across upgrades.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunMetadata {
+    pub lighthouse_version: String,
+    pub host_user_agent: String,
+}
+
+/// The shape of the page's DOM, from the `dom-size` audit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DomStats {
+    pub total_elements: u64,
+    pub max_depth: u64,
+    /// The most child elements any one parent has.
+    pub max_children: u64,
+}
+
+/// A Lighthouse "opportunity" audit and how much fixing it is estimated to save.
+#[derive(Debug, Clone, Serialize)]
+pub struct Opportunity {
+    pub id: String,
+    pub title: String,
+    pub savings_ms: f64,
+    pub savings_bytes: f64,
+}
+
+/// What one third-party entity (e.g. "Google Tag Manager") cost the page.
+#[derive(Debug, Clone, Serialize)]
+pub struct ThirdParty {
+    pub entity: String,
+    pub main_thread_ms: f64,
+    pub transfer_bytes: f64,
+}
+
+impl LighthouseRun {
+    /// Whether the audited page differs from `requested_url`, ignoring a trailing slash.
+    pub fn was_redirected(&self, requested_url: &str) -> bool {
+        !self.final_url.is_empty() && !same_url(&self.final_url, requested_url)
+    }
+}
+
+/// How often, and how patiently, a failed Lighthouse run is retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `attempt` (zero-based), doubling each time.
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        self.base_delay * 2u32.saturating_pow(attempt)
+    }
+}
 
 /// Runs Lighthouse and extracts performance metrics.
 ///
@@ -14,78 +797,613 @@ use crate::metrics::LighthouseMetrics;
 /// * `label` - Name of the scenario (for file naming).
 /// * `url` - URL to run Lighthouse against.
 /// * `blocked_patterns` - Optional URL patterns to block.
+/// * `options` - Run settings such as the cache state.
 ///
 /// # Returns
 ///
-/// * `Ok(LighthouseMetrics)` on success.
+/// * `Ok(LighthouseRun)` on success.
 /// * `Err(Box<dyn Error>)` on failure.
-pub async fn fetch_lighthouse_metrics(label: &str, url: &str, blocked_patterns: &[&str]) -> Result<LighthouseMetrics, Box<dyn Error>> {
+pub async fn fetch_lighthouse_metrics(
+    label: &str,
+    url: &str,
+    blocked_patterns: &[String],
+    options: &LighthouseOptions,
+) -> Result<LighthouseRun, Box<dyn Error>> {
+    let throttling_flags = options.throttling.flags();
+
+    let extra_headers_flag = match &options.extra_headers {
+        Some(raw) => {
+            validate_extra_headers(raw)?;
+            Some(format!("--extra-headers={}", raw))
+        }
+        None => None,
+    };
+
+    if let Some(proxy) = &options.proxy {
+        validate_proxy(proxy)?;
+    }
+
+    let mut chrome_flags: Vec<String> = options.chrome_flags.iter().cloned().collect();
+    if let Some(proxy) = &options.proxy {
+        chrome_flags.push(format!("--proxy-server={}", proxy));
+    }
+    if let Some(profile_dir) = &options.profile_dir {
+        chrome_flags.push(format!("--user-data-dir={}", profile_dir.display()));
+    }
+    let chrome_flags_flag = (!chrome_flags.is_empty()).then(|| format!("--chrome-flags={}", chrome_flags.join(" ")));
+
+    // With several outputs Lighthouse appends `.report.json` / `.report.html` to the path.
+    // Every run of this invocation shares the timestamp, so repeated runs of a scenario
+    // replace each other's report and the last one is kept.
+    let timestamp = run_timestamp(&options.timestamp_format);
+    let report_base = format!("lighthouse_report_{}_{}", label, timestamp);
+    let output_base = options.out_dir.join(&report_base);
+    let output_path_flag = format!("--output-path={}", output_base.display());
+    let config_path_flag = options.lh_config.as_ref().map(|path| format!("--config-path={}", path.display()));
+
     let mut args = vec![
         url,
         "--output=json",
-        "--output-path=stdout",
+        "--output=html",
+        &output_path_flag,
         "--quiet",
         "--window-size=1000,1000",
-        "--preset=desktop",
         "--headless",
-        "--only-categories=performance,accessibility,seo,best-practices",
         "--save-assets",
     ];
 
-    for pattern in blocked_patterns {
+    // A custom config sets its own form factor and categories; our defaults on the
+    // command line would override it.
+    match &config_path_flag {
+        Some(flag) => args.push(flag),
+        None => args.extend(["--preset=desktop", "--only-categories=performance,accessibility,seo,best-practices"]),
+    }
+
+    for pattern in merge_blocked_patterns(blocked_patterns, &options.denylist) {
         args.push("--blocked-url-patterns");
         args.push(pattern);
     }
 
-    let output = Command::new("lighthouse")
-        .args(&args)
-        .output()?;
+    args.extend(throttling_flags.iter().map(String::as_str));
 
-    if !output.status.success() {
-        return Err(format!("Lighthouse command failed with status: {}", output.status).into());
-    }
-
-    let stdout = String::from_utf8(output.stdout)?;
-    let json: Value = serde_json::from_str(&stdout)?;
-
-    let formatted_json = to_string_pretty(&json)?;
-    let date = Local::now().format("%Y-%m-%d").to_string();
-    let file_name = format!("lighthouse_report_{}_{}.json", label, date);
-
-    let mut file = File::create(&file_name).await?;
-    file.write_all(formatted_json.as_bytes()).await?;
-
-    println!("✅ Saved report: {}", file_name);
-
-    Ok(extract_metrics(&json))
-}
-
-/// Parses performance metrics from Lighthouse JSON.
-fn extract_metrics(json: &Value) -> LighthouseMetrics {
-    LighthouseMetrics {
-        first_contentful_paint: json["audits"]["first-contentful-paint"]["numericValue"].as_f64().unwrap_or(0.0),
-        largest_contentful_paint: json["audits"]["largest-contentful-paint"]["numericValue"].as_f64().unwrap_or(0.0),
-        time_to_interactive: json["audits"]["interactive"]["numericValue"].as_f64().unwrap_or(0.0),
-        total_blocking_time: json["audits"]["total-blocking-time"]["numericValue"].as_f64().unwrap_or(0.0),
-        cumulative_layout_shift: json["audits"]["cumulative-layout-shift"]["numericValue"].as_f64().unwrap_or(0.0),
-        speed_index: json["audits"]["speed-index"]["numericValue"].as_f64().unwrap_or(0.0),
-        performance_score: json["categories"]["performance"]["score"].as_f64().unwrap_or(0.0) * 100.0,
-        first_meaningful_paint: json["audits"]["first-meaningful-paint"]["numericValue"].as_f64().unwrap_or(0.0),
-        first_cpu_idle: json["audits"]["first-cpu-idle"]["numericValue"].as_f64().unwrap_or(0.0),
-        max_potential_fid: json["audits"]["max-potential-fid"]["numericValue"].as_f64().unwrap_or(0.0),
-        estimated_input_latency: json["audits"]["estimated-input-latency"]["numericValue"].as_f64().unwrap_or(0.0),
-        server_response_time: json["audits"]["server-response-time"]["numericValue"].as_f64().unwrap_or(0.0),
-        javascript_bootup_time: json["audits"]["bootup-time"]["numericValue"].as_f64().unwrap_or(0.0),
-        total_byte_weight: json["audits"]["total-byte-weight"]["numericValue"].as_f64().unwrap_or(0.0),
-        render_blocking_resources: json["audits"]["render-blocking-resources"]["numericValue"].as_f64().unwrap_or(0.0),
-        unused_javascript: json["audits"]["unused-javascript"]["numericValue"].as_f64().unwrap_or(0.0),
-        unused_css: json["audits"]["unused-css"]["numericValue"].as_f64().unwrap_or(0.0),
-        dom_size: json["audits"]["dom-size"]["numericValue"].as_f64().unwrap_or(0.0),
-        preconnect_origins: json["audits"]["preconnect-to-required-origins"]["numericValue"].as_f64().unwrap_or(0.0),
-        properly_sized_images: json["audits"]["uses-responsive-images"]["numericValue"].as_f64().unwrap_or(0.0),
-        efficiently_encoded_images: json["audits"]["uses-optimized-images"]["numericValue"].as_f64().unwrap_or(0.0),
-        minimize_main_thread_work: json["audits"]["mainthread-work-breakdown"]["numericValue"].as_f64().unwrap_or(0.0),
-        minimize_render_blocking_stylesheets: json["audits"]["uses-rel-preload"]["numericValue"].as_f64().unwrap_or(0.0),
-        avoid_large_layout_shifts: json["audits"]["layout-shift-elements"]["numericValue"].as_f64().unwrap_or(0.0),
+    if options.cache == CacheState::Warm {
+        args.push("--disable-storage-reset");
+    }
+    if let Some(flag) = &chrome_flags_flag {
+        args.push(flag);
+    }
+    if let Some(flag) = &extra_headers_flag {
+        args.push(flag);
+    }
+
+    if options.dry_run {
+        info!("🔎 Dry run [{}]: {} {}", label, options.lighthouse_path.display(), args.join(" "));
+        return Ok(LighthouseRun {
+            final_url: url.to_string(),
+            cache: options.cache,
+            ..Default::default()
+        });
+    }
+
+    // Clear leftovers from an interrupted run so a stale report is never mistaken for this one.
+    let raw_json_path = options.out_dir.join(format!("{}.report.json", report_base));
+    let raw_html_path = options.out_dir.join(format!("{}.report.html", report_base));
+    for stale in [&raw_json_path, &raw_html_path] {
+        if stale.exists() {
+            fs::remove_file(stale)?;
+        }
+    }
+
+    let mut command = tokio::process::Command::new(&options.lighthouse_path);
+    command.args(&args).stdout(Stdio::null()).stderr(Stdio::null());
+    if let Some(proxy) = &options.proxy {
+        set_proxy_env(&mut command, proxy);
+    }
+    // Own process group, so a timeout can take Chrome down with Lighthouse.
+    #[cfg(unix)]
+    command.process_group(0);
+    let mut child = command.spawn().map_err(|e| spawn_error(&options.lighthouse_path, e))?;
+
+    let status = match tokio::time::timeout(options.run_timeout, child.wait()).await {
+        Ok(status) => status?,
+        Err(_) => {
+            kill_process_tree(&mut child).await;
+            return Err(LighthouseError::TimedOut(options.run_timeout).into());
+        }
+    };
+
+    if !status.success() {
+        return Err(LighthouseError::Failed(status).into());
+    }
+
+    if !raw_json_path.exists() {
+        return Err(LighthouseError::InvalidOutput(format!("no report written to {}", raw_json_path.display())).into());
+    }
+
+    let json = parse_report_file(&raw_json_path, options.max_report_bytes)?;
+
+    if options.save_report {
+        let extension = if options.compress { "json.gz" } else { "json" };
+        let file_name = options.out_dir.join(format!("{}.{}", report_base, extension));
+        write_report_json(&file_name, &json, options.compress)?;
+        debug!("✅ Saved report: {}", file_name.display());
+    }
+    fs::remove_file(&raw_json_path)?;
+
+    if raw_html_path.exists() {
+        let html_name = options.out_dir.join(format!("{}.html", report_base));
+        fs::rename(&raw_html_path, &html_name)?;
+        debug!("✅ Saved HTML report: {}", html_name.display());
+    }
+
+    let filmstrip = extract_filmstrip(&json);
+    if options.save_filmstrip && !filmstrip.is_empty() {
+        let dir = options.out_dir.join(format!("filmstrip_{}_{}", label, timestamp));
+        save_filmstrip(&filmstrip, &dir)?;
+        debug!("✅ Saved {} filmstrip frames: {}", filmstrip.len(), dir.display());
+    }
+
+    let metrics = LighthouseMetrics::from_json_with(&json, &options.audit_map).map_err(LighthouseError::from)?;
+    if metrics.is_suspicious() {
+        return Err(LighthouseError::SuspiciousResult.into());
+    }
+
+    Ok(LighthouseRun {
+        metrics,
+        final_url: extract_final_url(&json).unwrap_or_else(|| url.to_string()),
+        redirect_ms: json["audits"]["redirects"]["numericValue"].as_f64().unwrap_or(0.0),
+        origins: extract_request_origins(&json),
+        cache: options.cache,
+        opportunities: extract_opportunities(&json),
+        metadata: extract_run_metadata(&json),
+        filmstrip,
+        byte_breakdown: extract_byte_breakdown(&json),
+        critical_chains: extract_critical_chains(&json),
+        third_parties: extract_third_parties(&json),
+        unused_javascript: extract_unused_javascript(&json),
+        dom: extract_dom_stats(&json),
+        // Lighthouse names saved assets after the output path, numbered per pass.
+        trace_path: Some(PathBuf::from(format!("{}-0.trace.json", output_base.display()))).filter(|path| path.exists()),
+    })
+}
+
+/// Reads the `screenshot-thumbnails` audit; reports without it yield no frames.
+pub fn extract_filmstrip(json: &Value) -> Vec<FilmstripFrame> {
+    json["audits"]["screenshot-thumbnails"]["details"]["items"]
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| {
+                    Some(FilmstripFrame {
+                        timing_ms: item["timing"].as_f64()?,
+                        data_uri: item["data"].as_str()?.to_string(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parses one level of the `chains` object (keyed by request ID) into chain requests.
+/// Lighthouse reports times in seconds; they're converted relative to `origin`.
+fn parse_chain_level(chains: &Value, origin: f64) -> Vec<ChainRequest> {
+    let Some(chains) = chains.as_object() else {
+        return Vec::new();
+    };
+    let mut requests: Vec<ChainRequest> = chains
+        .values()
+        .map(|node| {
+            let request = &node["request"];
+            ChainRequest {
+                url: request["url"].as_str().unwrap_or_default().to_string(),
+                start_ms: (request["startTime"].as_f64().unwrap_or(origin) - origin) * 1000.0,
+                end_ms: (request["endTime"].as_f64().unwrap_or(origin) - origin) * 1000.0,
+                transfer_size: request["transferSize"].as_f64().unwrap_or(0.0),
+                children: parse_chain_level(&node["children"], origin),
+            }
+        })
+        .collect();
+    requests.sort_by(|a, b| a.start_ms.total_cmp(&b.start_ms));
+    requests
+}
+
+/// Every root-to-leaf path through `node`.
+fn chain_paths(node: &ChainRequest) -> Vec<Vec<&ChainRequest>> {
+    if node.children.is_empty() {
+        return vec![vec![node]];
+    }
+    node.children
+        .iter()
+        .flat_map(chain_paths)
+        .map(|mut path| {
+            path.insert(0, node);
+            path
+        })
+        .collect()
+}
+
+/// Reads the `critical-request-chains` audit: the requests that block first render and
+/// what triggered them. Reports without it yield empty chains.
+pub fn extract_critical_chains(json: &Value) -> CriticalChains {
+    let chains = &json["audits"]["critical-request-chains"]["details"]["chains"];
+    let origin = chains
+        .as_object()
+        .and_then(|roots| roots.values().filter_map(|node| node["request"]["startTime"].as_f64()).reduce(f64::min))
+        .unwrap_or(0.0);
+    let roots = parse_chain_level(chains, origin);
+
+    let paths: Vec<Vec<&ChainRequest>> = roots.iter().flat_map(chain_paths).collect();
+    let max_depth = paths.iter().map(Vec::len).max().unwrap_or(0);
+    let duration = |path: &[&ChainRequest]| match (path.first(), path.last()) {
+        (Some(first), Some(last)) => last.end_ms - first.start_ms,
+        _ => 0.0,
+    };
+    let longest = paths.iter().max_by(|a, b| duration(a).total_cmp(&duration(b)));
+    let longest_path = longest.map(|path| path.iter().map(|r| r.url.clone()).collect()).unwrap_or_default();
+    let longest_duration_ms = longest.map(|path| duration(path)).unwrap_or(0.0);
+
+    CriticalChains { max_depth, longest_path, longest_duration_ms, roots }
+}
+
+/// Writes each frame to `dir` as `frame_<index>_<timing>ms.<ext>`.
+pub fn save_filmstrip(frames: &[FilmstripFrame], dir: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for (i, frame) in frames.iter().enumerate() {
+        match frame.decode() {
+            Some((extension, bytes)) => {
+                fs::write(dir.join(format!("frame_{:02}_{:.0}ms.{}", i, frame.timing_ms, extension)), bytes)?;
+            }
+            None => warn!("⚠️ Filmstrip frame at {:.0} ms isn't base64 image data; skipped", frame.timing_ms),
+        }
+    }
+    Ok(())
+}
+
+/// Runs [`fetch_lighthouse_metrics`], retrying with exponential backoff.
+///
+/// Only errors for which [`LighthouseError::is_retryable`] holds are retried; any
+/// other error (e.g. the binary is missing, the report is too large, or it can't be
+/// saved) is returned immediately.
+pub async fn fetch_lighthouse_metrics_with_retry(
+    label: &str,
+    url: &str,
+    blocked_patterns: &[String],
+    options: &LighthouseOptions,
+    retry: &RetryPolicy,
+) -> Result<LighthouseRun, Box<dyn Error>> {
+    let mut attempt = 0;
+    loop {
+        // The error is dropped before sleeping, so this future stays `Send` for the runner's tasks.
+        let delay = match fetch_lighthouse_metrics(label, url, blocked_patterns, options).await {
+            Ok(run) => return Ok(run),
+            Err(e) if attempt < retry.max_retries && e.downcast_ref::<LighthouseError>().is_some_and(LighthouseError::is_retryable) => {
+                let delay = retry.delay_for(attempt);
+                attempt += 1;
+                warn!(
+                    "⚠️ {} (retry {}/{} in {}ms)",
+                    e,
+                    attempt,
+                    retry.max_retries,
+                    delay.as_millis()
+                );
+                delay
+            }
+            Err(e) => return Err(e),
+        };
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Collects every opportunity audit's estimated savings, sorted by `savings_ms` descending.
+pub fn extract_opportunities(json: &Value) -> Vec<Opportunity> {
+    let Some(audits) = json["audits"].as_object() else {
+        return Vec::new();
+    };
+
+    let mut opportunities: Vec<Opportunity> = audits
+        .iter()
+        .filter(|(_, audit)| audit["details"]["type"].as_str() == Some("opportunity"))
+        .map(|(id, audit)| Opportunity {
+            id: id.clone(),
+            title: audit["title"].as_str().unwrap_or(id).to_string(),
+            savings_ms: audit["details"]["overallSavingsMs"].as_f64().unwrap_or(0.0),
+            savings_bytes: audit["details"]["overallSavingsBytes"].as_f64().unwrap_or(0.0),
+        })
+        .collect();
+
+    opportunities.sort_by(|a, b| b.savings_ms.partial_cmp(&a.savings_ms).unwrap_or(std::cmp::Ordering::Equal));
+    opportunities
+}
+
+/// Origins present in one scenario but not the other.
+#[derive(Debug, Clone, Default)]
+pub struct OriginDiff {
+    /// Hosts requested in the baseline that no longer appear.
+    pub removed: BTreeSet<String>,
+    /// Hosts that appear only in the compared scenario.
+    pub added: BTreeSet<String>,
+}
+
+/// Collects the distinct request hosts from the `network-requests` audit.
+pub fn extract_request_origins(json: &Value) -> BTreeSet<String> {
+    json["audits"]["network-requests"]["details"]["items"]
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item["url"].as_str())
+                .filter_map(|url| Url::parse(url).ok())
+                .filter_map(|url| url.host_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Reads the `third-party-summary` audit, most main-thread time first; empty if the
+/// report has no such audit.
+pub fn extract_third_parties(json: &Value) -> Vec<ThirdParty> {
+    let Some(items) = json["audits"]["third-party-summary"]["details"]["items"].as_array() else {
+        return Vec::new();
+    };
+    let mut third_parties: Vec<ThirdParty> = items
+        .iter()
+        .filter_map(|item| {
+            // Older reports give the entity as a link object rather than a name.
+            let entity = item["entity"].as_str().or_else(|| item["entity"]["text"].as_str())?;
+            Some(ThirdParty {
+                entity: entity.to_string(),
+                main_thread_ms: item["mainThreadTime"].as_f64().unwrap_or(0.0),
+                transfer_bytes: item["transferSize"].as_f64().unwrap_or(0.0),
+            })
+        })
+        .collect();
+    third_parties.sort_by(|a, b| b.main_thread_ms.total_cmp(&a.main_thread_ms));
+    third_parties
+}
+
+/// Reads the `unused-javascript` audit as `(url, wasted_bytes, total_bytes)` per script,
+/// most wasted bytes first; empty if the audit has no details (e.g. nothing to report).
+pub fn extract_unused_javascript(json: &Value) -> Vec<(String, f64, f64)> {
+    let Some(items) = json["audits"]["unused-javascript"]["details"]["items"].as_array() else {
+        return Vec::new();
+    };
+    let mut scripts: Vec<(String, f64, f64)> = items
+        .iter()
+        .filter_map(|item| {
+            let url = item["url"].as_str()?.to_string();
+            Some((url, item["wastedBytes"].as_f64().unwrap_or(0.0), item["totalBytes"].as_f64().unwrap_or(0.0)))
+        })
+        .collect();
+    scripts.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scripts
+}
+
+/// Reads the `dom-size` audit's three statistics, which Lighthouse lists in the order
+/// total elements, maximum depth, maximum children. Values may be numbers, `{ "value": n }`
+/// objects or (in older reports) strings such as "1,234"; missing ones read 0.
+pub fn extract_dom_stats(json: &Value) -> DomStats {
+    let items = json["audits"]["dom-size"]["details"]["items"].as_array();
+    let statistic = |i: usize| {
+        let value = &items.and_then(|items| items.get(i)).unwrap_or(&Value::Null)["value"];
+        value
+            .as_u64()
+            .or_else(|| value["value"].as_u64())
+            .or_else(|| value.as_str()?.replace(',', "").trim().parse().ok())
+            .unwrap_or(0)
+    };
+    DomStats { total_elements: statistic(0), max_depth: statistic(1), max_children: statistic(2) }
+}
+
+/// Sums transfer sizes by `resourceType` (Script, Image, Stylesheet, ...), largest first.
+///
+/// Uses the `network-requests` audit, falling back to the `resource-summary` audit found
+/// in older reports; returns an empty list when neither is present.
+pub fn extract_byte_breakdown(json: &Value) -> Vec<(String, f64)> {
+    let mut totals: Vec<(String, f64)> = Vec::new();
+
+    if let Some(items) = json["audits"]["network-requests"]["details"]["items"].as_array() {
+        for item in items {
+            let resource_type = item["resourceType"].as_str().unwrap_or("Other");
+            let bytes = item["transferSize"].as_f64().unwrap_or(0.0);
+            match totals.iter_mut().find(|(t, _)| t == resource_type) {
+                Some((_, total)) => *total += bytes,
+                None => totals.push((resource_type.to_string(), bytes)),
+            }
+        }
+    } else if let Some(items) = json["audits"]["resource-summary"]["details"]["items"].as_array() {
+        totals = items
+            .iter()
+            .filter(|item| item["resourceType"].as_str() != Some("total"))
+            .filter_map(|item| Some((item["label"].as_str()?.to_string(), item["transferSize"].as_f64()?)))
+            .collect();
+    }
+
+    totals.sort_by(|a, b| b.1.total_cmp(&a.1));
+    totals
+}
+
+/// Compares the origins of `other` against `baseline`.
+pub fn diff_origins(baseline: &BTreeSet<String>, other: &BTreeSet<String>) -> OriginDiff {
+    OriginDiff {
+        removed: baseline.difference(other).cloned().collect(),
+        added: other.difference(baseline).cloned().collect(),
+    }
+}
+
+/// Reads the post-redirect URL, preferring the newer `finalDisplayedUrl` field.
+pub fn extract_final_url(json: &Value) -> Option<String> {
+    ["finalDisplayedUrl", "finalUrl", "mainDocumentUrl"]
+        .iter()
+        .find_map(|key| json[*key].as_str())
+        .map(str::to_string)
+}
+
+/// Lists `(audit id, error message)` for every audit Lighthouse could not compute.
+pub(crate) fn errored_audits(audits: &Map<String, Value>) -> impl Iterator<Item = (&str, &str)> {
+    audits
+        .iter()
+        .filter(|(_, audit)| audit["scoreDisplayMode"].as_str() == Some("error"))
+        .map(|(id, audit)| (id.as_str(), audit["errorMessage"].as_str().unwrap_or("unknown error")))
+}
+
+/// Reads the Lighthouse version and the Chrome user agent from a report.
+pub fn extract_run_metadata(json: &Value) -> RunMetadata {
+    RunMetadata {
+        lighthouse_version: json["lighthouseVersion"].as_str().unwrap_or_default().to_string(),
+        host_user_agent: json["environment"]["hostUserAgent"].as_str().unwrap_or_default().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn normalize_url_merges_common_variants() {
+        let canonical = "https://alaskaair.com";
+        for variant in [
+            "https://alaskaair.com",
+            "https://alaskaair.com/",
+            "https://AlaskaAir.com/",
+            "HTTPS://alaskaair.com:443",
+            "https://alaskaair.com/#top",
+            " https://alaskaair.com/ ",
+        ] {
+            assert_eq!(normalize_url(variant), canonical, "{}", variant);
+        }
+    }
+
+    #[test]
+    fn normalize_url_keeps_what_changes_the_page() {
+        assert_eq!(normalize_url("https://example.com/Deals/"), "https://example.com/Deals");
+        assert_eq!(normalize_url("https://example.com/search/?q=SEA"), "https://example.com/search?q=SEA");
+        assert_eq!(normalize_url("http://example.com:8080/"), "http://example.com:8080");
+        assert_eq!(normalize_url("http://example.com:80/"), "http://example.com");
+        assert!(!same_url("http://example.com", "https://example.com"));
+    }
+
+    #[test]
+    fn expand_env_vars_substitutes_and_falls_back() {
+        std::env::set_var("PERF_TRACKER_TEST_HOST", "https://staging.example.com");
+        std::env::remove_var("PERF_TRACKER_TEST_UNSET");
+
+        assert_eq!(
+            expand_env_vars("${PERF_TRACKER_TEST_HOST}/checkout").unwrap(),
+            "https://staging.example.com/checkout"
+        );
+        assert_eq!(
+            expand_env_vars("${PERF_TRACKER_TEST_UNSET:-https://example.com}/deals").unwrap(),
+            "https://example.com/deals"
+        );
+        assert_eq!(expand_env_vars("https://example.com/$5-fares").unwrap(), "https://example.com/$5-fares");
+        assert!(expand_env_vars("${PERF_TRACKER_TEST_UNSET}/checkout").is_err());
+        assert!(expand_env_vars("${PERF_TRACKER_TEST_HOST").is_err());
+    }
+
+    #[test]
+    fn normalize_url_passes_through_unparseable_input() {
+        assert_eq!(normalize_url(" not a url "), "not a url");
+    }
+
+    #[test]
+    fn third_parties_sort_by_main_thread_time() {
+        let report = serde_json::json!({
+            "audits": { "third-party-summary": { "details": { "items": [
+                { "entity": "Tealium", "mainThreadTime": 120.0, "transferSize": 40960 },
+                { "entity": { "type": "link", "text": "Google Tag Manager" }, "mainThreadTime": 480.5, "transferSize": 102400 },
+                { "mainThreadTime": 999.0 },
+            ] } } },
+        });
+
+        let third_parties = extract_third_parties(&report);
+
+        let names: Vec<&str> = third_parties.iter().map(|t| t.entity.as_str()).collect();
+        assert_eq!(names, ["Google Tag Manager", "Tealium"]);
+        assert_eq!(third_parties[0].transfer_bytes, 102400.0);
+        assert!(extract_third_parties(&serde_json::json!({})).is_empty());
+    }
+
+    #[test]
+    fn unused_javascript_sorts_by_wasted_bytes() {
+        let report = serde_json::json!({
+            "audits": { "unused-javascript": { "details": { "items": [
+                { "url": "https://example.com/app.js", "wastedBytes": 20480, "totalBytes": 81920 },
+                { "url": "https://cdn.example.com/vendor.js", "wastedBytes": 153600, "totalBytes": 204800 },
+                { "wastedBytes": 1 },
+            ] } } },
+        });
+
+        let scripts = extract_unused_javascript(&report);
+
+        assert_eq!(scripts.len(), 2);
+        assert_eq!(scripts[0], ("https://cdn.example.com/vendor.js".to_string(), 153600.0, 204800.0));
+        assert_eq!(scripts[1].0, "https://example.com/app.js");
+        assert!(extract_unused_javascript(&serde_json::json!({ "audits": { "unused-javascript": {} } })).is_empty());
+    }
+
+    #[test]
+    fn dom_stats_read_numeric_and_legacy_string_values() {
+        let report = serde_json::json!({
+            "audits": { "dom-size": { "details": { "items": [
+                { "statistic": "Total DOM Elements", "value": { "type": "numeric", "value": 1843 } },
+                { "statistic": "Maximum DOM Depth", "value": 27 },
+                { "statistic": "Maximum Child Elements", "value": "1,024" },
+            ] } } },
+        });
+
+        let dom = extract_dom_stats(&report);
+
+        assert_eq!(dom, DomStats { total_elements: 1843, max_depth: 27, max_children: 1024 });
+        assert_eq!(extract_dom_stats(&serde_json::json!({})), DomStats::default());
+    }
+
+    #[test]
+    fn validate_proxy_accepts_only_host_and_port() {
+        for ok in ["http://proxy.corp:3128", "https://proxy.corp", "socks5://10.0.0.1:1080"] {
+            assert!(validate_proxy(ok).is_ok(), "{}", ok);
+        }
+        for bad in ["proxy.corp:3128", "ftp://proxy.corp", "http://user:pw@proxy.corp", "http://proxy.corp/path"] {
+            assert!(validate_proxy(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn parse_report_file_tolerates_invalid_utf8_and_enforces_the_limit() {
+        let dir = TempDir::new("parse");
+        let garbled = dir.join("garbled.json");
+        fs::write(&garbled, b"{\"requestedUrl\": \"https://example.com/\xff\"}").unwrap();
+        let gzipped = dir.join("large.json.gz");
+        let large = serde_json::json!({ "padding": "x".repeat(4096) });
+        write_report_json(&gzipped, &large, true).unwrap();
+
+        let parsed = parse_report_file(&garbled, u64::MAX).unwrap();
+        let within_limit = parse_report_file(&gzipped, 8192).unwrap();
+        // The compressed file is tiny; the limit applies to what it decompresses to.
+        let over_limit = parse_report_file(&gzipped, 1024).unwrap_err().to_string();
+
+        assert_eq!(parsed["requestedUrl"], "https://example.com/\u{FFFD}");
+        assert_eq!(within_limit, large);
+        assert!(over_limit.contains("report limit"), "{}", over_limit);
+    }
+
+    #[test]
+    fn load_report_rejects_json_that_is_not_a_report() {
+        let dir = TempDir::new("load");
+        let report = dir.join("report.json.gz");
+        write_report_json(&report, &serde_json::json!({ "lighthouseVersion": "12.0.0", "audits": {} }), true).unwrap();
+        let summary = dir.join("summary.json");
+        fs::write(&summary, "[]").unwrap();
+        let no_audits = dir.join("no-audits.json");
+        fs::write(&no_audits, r#"{"lighthouseVersion": "12.0.0"}"#).unwrap();
+
+        let loaded = load_report(&report, u64::MAX).unwrap();
+        let not_a_report = load_report(&summary, u64::MAX).unwrap_err().to_string();
+        let without_audits = load_report(&no_audits, u64::MAX).unwrap_err().to_string();
+
+        assert_eq!(loaded["lighthouseVersion"], "12.0.0");
+        assert!(not_a_report.ends_with("summary.json is not a Lighthouse report (it has no `lighthouseVersion`)"), "{}", not_a_report);
+        assert!(without_audits.contains("no `audits`"), "{}", without_audits);
     }
 }
@@ -1,179 +1,177 @@
-use dotenv::dotenv;
-use std::error::Error;
 use std::fs;
-use std::process::Command;
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
-use serde::{Serialize, Deserialize};
-use chrono::Local;
-use serde_json::to_string_pretty;
-
-#[derive(Debug, Serialize, Deserialize, Default)]
-struct LighthouseMetrics {
-    first_contentful_paint: f64,
-    largest_contentful_paint: f64,
-    time_to_interactive: f64,
-    total_blocking_time: f64,
-    cumulative_layout_shift: f64,
-    speed_index: f64,
-    performance_score: f64,
-    first_meaningful_paint: f64,
-    first_cpu_idle: f64,
-    max_potential_fid: f64,
-    estimated_input_latency: f64,
-    server_response_time: f64,
-    javascript_bootup_time: f64,
-    total_byte_weight: f64,
-    render_blocking_resources: f64,
-    unused_javascript: f64,
-    unused_css: f64,
-    dom_size: f64,
-    preconnect_origins: f64,
-    properly_sized_images: f64,
-    efficiently_encoded_images: f64,
-    minimize_main_thread_work: f64,
-    minimize_render_blocking_stylesheets: f64,
-    avoid_large_layout_shifts: f64,
+use std::io;
+
+use log::info;
+use unicode_width::UnicodeWidthStr;
+
+use crate::config::OutputConfig;
+use crate::metrics::LighthouseMetrics;
+
+/// One row of the markdown scenario table.
+pub struct ScenarioMetrics {
+    pub name: String,
+    pub perf_score: f64,
+    pub fcp: f64,
+    pub lcp: f64,
+    pub tti: f64,
+    pub tbt: f64,
+    pub cls: f64,
+    pub delta_perf: f64,
+    /// Performance score as a percentage of the baseline's; `None` without a usable baseline.
+    pub perf_pct_of_baseline: Option<f64>,
+    /// LCP divided by the baseline's LCP; `None` without a usable baseline.
+    pub lcp_ratio: Option<f64>,
+    /// TBT divided by the baseline's TBT; `None` without a usable baseline.
+    pub tbt_ratio: Option<f64>,
 }
 
-struct ScenarioMetrics {
-    name: String,
-    perf_score: f64,
-    fcp: f64,
-    lcp: f64,
-    tti: f64,
-    tbt: f64,
-    delta_perf: f64,
+impl ScenarioMetrics {
+    /// Builds a row from metrics already converted with `to_seconds`.
+    pub fn new(name: &str, metrics: &LighthouseMetrics) -> Self {
+        ScenarioMetrics {
+            name: name.to_string(),
+            perf_score: metrics.performance_score,
+            fcp: metrics.first_contentful_paint,
+            lcp: metrics.largest_contentful_paint,
+            tti: metrics.time_to_interactive,
+            tbt: metrics.total_blocking_time,
+            cls: metrics.cumulative_layout_shift,
+            delta_perf: 0.0,
+            perf_pct_of_baseline: None,
+            lcp_ratio: None,
+            tbt_ratio: None,
+        }
+    }
 }
 
-
-
-
-async fn fetch_lighthouse_metrics(label: &str, url: &str, blocked_patterns: &[&str]) -> Result<LighthouseMetrics, Box<dyn Error>> {
-    let mut args = vec![
-        url,
-        "--output=json",
-        "--output-path=stdout",
-        "--quiet",
-        "--window-size=1000,1000",
-        "--preset=desktop",
-        "--headless",
-        "--only-categories=performance,accessibility,seo,best-practices",
-    ];
-
-    for pattern in blocked_patterns {
-        args.push("--blocked-url-patterns");
-        args.push(pattern);
+/// Fills in each row's score delta and ratios against the `baseline` scenario and
+/// sorts the rows so the biggest improvements come first. A ratio whose baseline
+/// value is zero is left as `None`.
+///
+/// Returns `false` when there is no `baseline` row, in which case the deltas are
+/// measured from zero and shouldn't be shown as deltas.
+pub fn rank_against_baseline(summary_data: &mut [ScenarioMetrics]) -> bool {
+    let baseline = summary_data
+        .iter()
+        .find(|s| s.name == "baseline")
+        .map(|s| (s.perf_score, s.lcp, s.tbt));
+    let baseline_score = baseline.map_or(0.0, |(score, _, _)| score);
+    let ratio = |value: f64, base: f64| (base != 0.0).then(|| value / base);
+
+    for item in summary_data.iter_mut() {
+        item.delta_perf = item.perf_score - baseline_score;
+        if let Some((score, lcp, tbt)) = baseline {
+            item.perf_pct_of_baseline = ratio(item.perf_score, score).map(|r| r * 100.0);
+            item.lcp_ratio = ratio(item.lcp, lcp);
+            item.tbt_ratio = ratio(item.tbt, tbt);
+        }
     }
 
-    let output = Command::new("lighthouse")
-        .args(&args)
-        .output()?;
+    summary_data.sort_by(|a, b| b.delta_perf.partial_cmp(&a.delta_perf).unwrap_or(std::cmp::Ordering::Equal));
+    baseline.is_some()
+}
 
-    if !output.status.success() {
-        return Err(format!(
-            "Lighthouse command failed with status: {}",
-            output.status
-        ).into());
+/// Pads `cell` to `width` terminal columns, counting wide and combining characters by
+/// display width rather than bytes or `char`s, so non-ASCII names keep the table aligned.
+fn pad(cell: &str, width: usize, left_align: bool) -> String {
+    let fill = " ".repeat(width.saturating_sub(UnicodeWidthStr::width(cell)));
+    if left_align {
+        format!("{}{}", cell, fill)
+    } else {
+        format!("{}{}", fill, cell)
     }
-
-    let stdout = String::from_utf8(output.stdout)?;
-    let json: serde_json::Value = serde_json::from_str(&stdout)?;
-    let formatted_json = to_string_pretty(&json)?;
-
-    let current_date = Local::now().format("%Y-%m-%d").to_string();
-    let file_name = format!("lighthouse_report_{}_{}.json", label, current_date);
-    let mut file = File::create(&file_name).await?;
-    file.write_all(formatted_json.as_bytes()).await?;
-
-    println!("Saved full report for scenario '{}'", label);
-
-    Ok(LighthouseMetrics {
-        first_contentful_paint: json["audits"]["first-contentful-paint"]["numericValue"].as_f64().unwrap_or(0.0),
-        largest_contentful_paint: json["audits"]["largest-contentful-paint"]["numericValue"].as_f64().unwrap_or(0.0),
-        time_to_interactive: json["audits"]["interactive"]["numericValue"].as_f64().unwrap_or(0.0),
-        total_blocking_time: json["audits"]["total-blocking-time"]["numericValue"].as_f64().unwrap_or(0.0),
-        cumulative_layout_shift: json["audits"]["cumulative-layout-shift"]["numericValue"].as_f64().unwrap_or(0.0),
-        speed_index: json["audits"]["speed-index"]["numericValue"].as_f64().unwrap_or(0.0),
-        performance_score: json["categories"]["performance"]["score"].as_f64().unwrap_or(0.0) * 100.0,
-        first_meaningful_paint: json["audits"]["first-meaningful-paint"]["numericValue"].as_f64().unwrap_or(0.0),
-        first_cpu_idle: json["audits"]["first-cpu-idle"]["numericValue"].as_f64().unwrap_or(0.0),
-        max_potential_fid: json["audits"]["max-potential-fid"]["numericValue"].as_f64().unwrap_or(0.0),
-        estimated_input_latency: json["audits"]["estimated-input-latency"]["numericValue"].as_f64().unwrap_or(0.0),
-        server_response_time: json["audits"]["server-response-time"]["numericValue"].as_f64().unwrap_or(0.0),
-        javascript_bootup_time: json["audits"]["bootup-time"]["numericValue"].as_f64().unwrap_or(0.0),
-        total_byte_weight: json["audits"]["total-byte-weight"]["numericValue"].as_f64().unwrap_or(0.0),
-        render_blocking_resources: json["audits"]["render-blocking-resources"]["numericValue"].as_f64().unwrap_or(0.0),
-        unused_javascript: json["audits"]["unused-javascript"]["numericValue"].as_f64().unwrap_or(0.0),
-        unused_css: json["audits"]["unused-css"]["numericValue"].as_f64().unwrap_or(0.0),
-        dom_size: json["audits"]["dom-size"]["numericValue"].as_f64().unwrap_or(0.0),
-        preconnect_origins: json["audits"]["preconnect-to-required-origins"]["numericValue"].as_f64().unwrap_or(0.0),
-        properly_sized_images: json["audits"]["uses-responsive-images"]["numericValue"].as_f64().unwrap_or(0.0),
-        efficiently_encoded_images: json["audits"]["uses-optimized-images"]["numericValue"].as_f64().unwrap_or(0.0),
-        minimize_main_thread_work: json["audits"]["mainthread-work-breakdown"]["numericValue"].as_f64().unwrap_or(0.0),
-        minimize_render_blocking_stylesheets: json["audits"]["uses-rel-preload"]["numericValue"].as_f64().unwrap_or(0.0),
-        avoid_large_layout_shifts: json["audits"]["layout-shift-elements"]["numericValue"].as_f64().unwrap_or(0.0),
-    })
 }
 
-pub async fn run_lighthouse_scenarios() -> Result<(), Box<dyn std::error::Error>> {
-
-    dotenv().ok();
-
-    let scenarios = vec![
-        ("baseline", "https://alaskaair.com", vec![]),
-        ("no-tealium", "https://alaskaair.com", vec!["*.tealiumiq.com"]),
-        ("no-appd", "https://alaskaair.com", vec!["*.appdynamics.com"]),
-        ("no-optimizely", "https://alaskaair.com", vec!["*.optimizely.com"]),
-        ("no-header-footer", "https://alaskaair.com", vec!["*/header*", "*/footer*"]),
-        ("no-quantum", "https://alaskaair.com", vec!["*.quantummetric.com"]),
+/// Renders rows already ranked with [`rank_against_baseline`] as a markdown document.
+/// Columns are as wide as their widest cell, measured by display width.
+pub fn render_markdown_summary(summary_data: &[ScenarioMetrics]) -> String {
+    const HEADER: [&str; 10] = [
+        "Scenario",
+        "Perf",
+        "ΔPerf",
+        "% of Baseline",
+        "FCP",
+        "LCP",
+        "TTI",
+        "TBT",
+        "LCP × Baseline",
+        "TBT × Baseline",
     ];
 
-    let date = Local::now().format("%Y-%m-%d").to_string();
-    let mut summary_data = Vec::new();
-    let mut baseline_score = 0.0;
-
-    for (label, url, blocked) in scenarios {
-        println!("\n=== Running Scenario: {} ===", label);
-
-        let metrics = fetch_lighthouse_metrics(label, url, &blocked).await?;
-
-        if label == "baseline" {
-            baseline_score = metrics.performance_score;
+    let or_na = |value: Option<f64>, format: fn(f64) -> String| value.map_or_else(|| "n/a".to_string(), format);
+    let rows: Vec<[String; 10]> = summary_data
+        .iter()
+        .map(|s| {
+            [
+                s.name.clone(),
+                format!("{:.1}", s.perf_score),
+                format!("{:+.1}", s.delta_perf),
+                or_na(s.perf_pct_of_baseline, |p| format!("{:.1}%", p)),
+                LighthouseMetrics::format_field("first_contentful_paint", s.fcp),
+                LighthouseMetrics::format_field("largest_contentful_paint", s.lcp),
+                LighthouseMetrics::format_field("time_to_interactive", s.tti),
+                LighthouseMetrics::format_field("total_blocking_time", s.tbt),
+                or_na(s.lcp_ratio, |r| format!("{:.2}×", r)),
+                or_na(s.tbt_ratio, |r| format!("{:.2}×", r)),
+            ]
+        })
+        .collect();
+
+    let mut widths = HEADER.map(UnicodeWidthStr::width);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(UnicodeWidthStr::width(cell.as_str()));
         }
-
-        summary_data.push(ScenarioMetrics {
-            name: label.to_string(),
-            perf_score: metrics.performance_score,
-            fcp: metrics.first_contentful_paint / 1000.0,
-            lcp: metrics.largest_contentful_paint / 1000.0,
-            tti: metrics.time_to_interactive / 1000.0,
-            tbt: metrics.total_blocking_time / 1000.0,
-            delta_perf: 0.0,
-        });
     }
 
-    for item in &mut summary_data {
-        item.delta_perf = item.perf_score - baseline_score;
+    let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+    let mut markdown = String::from("# Lighthouse Performance Summary\n\n");
+    markdown.push_str(&line(HEADER.iter().zip(widths).map(|(h, w)| pad(h, w, true)).collect()));
+    markdown.push_str(&line(widths.iter().map(|&w| "-".repeat(w)).collect()));
+    for row in &rows {
+        markdown.push_str(&line(
+            row.iter().zip(widths).enumerate().map(|(i, (cell, w))| pad(cell, w, i == 0)).collect(),
+        ));
     }
+    markdown
+}
 
-    summary_data.sort_by(|a, b| b.delta_perf.partial_cmp(&a.delta_perf).unwrap_or(std::cmp::Ordering::Equal));
-
-    let mut markdown = String::new();
-    markdown.push_str("# Lighthouse Performance Summary\n\n");
-    markdown.push_str("| Scenario           | Perf | ΔPerf | FCP   | LCP   | TTI   | TBT  |\n");
-    markdown.push_str("|--------------------|------|-------|-------|-------|-------|------|\n");
-
-    for s in &summary_data {
-        markdown.push_str(&format!(
-            "| {:<18} | {:>4.1} | {:>+6.1} | {:>4.2}s | {:>4.2}s | {:>4.2}s | {:>4.2}s |\n",
-            s.name, s.perf_score, s.delta_perf, s.fcp, s.lcp, s.tti, s.tbt
-        ));
+/// Writes `summary_<timestamp>.md` from rows already ranked with [`rank_against_baseline`].
+pub fn write_markdown_summary(summary_data: &[ScenarioMetrics], output: &OutputConfig) -> io::Result<()> {
+    if !output.write_markdown {
+        return Ok(());
     }
 
-    let summary_filename = format!("summary_{}.md", date);
-    fs::write(&summary_filename, markdown)?;
-    println!("Markdown summary written to {}", summary_filename);
+    let summary_filename = output.out_dir.join(format!("summary_{}.md", output.timestamp()));
+    fs::write(&summary_filename, render_markdown_summary(summary_data))?;
+    info!("Markdown summary written to {}", summary_filename.display());
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_is_utf8_delta_and_columns_align_by_display_width() {
+        let mut rows = vec![
+            ScenarioMetrics::new("baseline", &LighthouseMetrics { performance_score: 80.0, ..Default::default() }),
+            ScenarioMetrics::new("sans-tiers-café", &LighthouseMetrics::default()),
+            ScenarioMetrics::new("無料配送", &LighthouseMetrics::default()),
+        ];
+        rank_against_baseline(&mut rows);
+
+        let markdown = render_markdown_summary(&rows);
+        let header = markdown.lines().nth(2).unwrap();
+        assert!(header.as_bytes().windows(2).any(|w| w == [0xCE, 0x94]), "Δ should be U+0394 in UTF-8");
+        assert!(header.contains("| ΔPerf |"));
+        assert!(!header.contains("Î"));
+
+        let table: Vec<&str> = markdown.lines().skip(2).collect();
+        let width = UnicodeWidthStr::width(table[0]);
+        for line in &table {
+            assert_eq!(UnicodeWidthStr::width(*line), width, "{}", line);
+        }
+    }
+}
@@ -1,205 +1,734 @@
+use clap::ValueEnum;
+use log::warn;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::process::Command;
-use chrono::Local;
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
-use serde_json::{to_string_pretty, Value};
-
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct LighthouseMetrics {
-    pub first_contentful_paint: f64,
-    pub largest_contentful_paint: f64,
-    pub time_to_interactive: f64,
-    pub total_blocking_time: f64,
-    pub cumulative_layout_shift: f64,
-    pub speed_index: f64,
-    pub performance_score: f64,
-    pub first_meaningful_paint: f64,
-    pub first_cpu_idle: f64,
-    pub max_potential_fid: f64,
-    pub estimated_input_latency: f64,
-    pub server_response_time: f64,
-    pub javascript_bootup_time: f64,
-    pub total_byte_weight: f64,
-    pub render_blocking_resources: f64,
-    pub unused_javascript: f64,
-    pub unused_css: f64,
-    pub dom_size: f64,
-    pub preconnect_origins: f64,
-    pub properly_sized_images: f64,
-    pub efficiently_encoded_images: f64,
-    pub minimize_main_thread_work: f64,
-    pub minimize_render_blocking_stylesheets: f64,
-    pub avoid_large_layout_shifts: f64,
+use std::fmt;
+use serde_json::Value;
+
+use crate::lighthouse::{errored_audits, AuditMap};
+
+/// How repeated runs of a scenario are combined into one result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Aggregation {
+    /// Average every field across runs.
+    #[default]
+    Mean,
+    /// Take each field's median across runs.
+    Median,
+    /// Keep the full metrics of the run with the highest performance score.
+    Best,
+    /// Keep the full metrics of the run with the lowest performance score.
+    Worst,
+}
+
+/// What a metric field measures, which decides how it is converted and displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// A timing Lighthouse reports in milliseconds; [`LighthouseMetrics::to_seconds`] converts it.
+    Millis,
+    /// An opportunity's estimated savings in milliseconds, left unconverted.
+    SavingsMillis,
+    Bytes,
+    /// A number of things, e.g. DOM elements.
+    Count,
+    /// A ratio without a unit, e.g. layout shift.
+    Unitless,
+    /// A category score from 0 to 100.
+    Score,
+}
+
+impl Unit {
+    /// How fields of this unit are shown unless the field table picks another [`Format`].
+    pub fn default_format(self) -> Format {
+        match self {
+            Unit::Millis => Format::Seconds,
+            Unit::SavingsMillis => Format::Ms,
+            Unit::Bytes => Format::KiB,
+            Unit::Count => Format::Integer,
+            Unit::Unitless => Format::Decimal,
+            Unit::Score => Format::Score,
+        }
+    }
+}
+
+/// How a metric is shown to people: its display unit and precision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Seconds with two decimals, e.g. `3.30 s`.
+    Seconds,
+    /// Whole milliseconds, e.g. `150 ms`, for timings that are usually well under a second.
+    Ms,
+    /// Whole kibibytes, e.g. `812 KiB`.
+    KiB,
+    Integer,
+    /// Three decimals, e.g. a layout shift of `0.083`.
+    Decimal,
+    /// One decimal, e.g. a score of `87.5`.
+    Score,
+}
+
+impl Format {
+    /// Renders `value`, given in the field's Lighthouse unit (milliseconds for timings).
+    pub fn apply(self, value: f64) -> String {
+        match self {
+            Format::Seconds => format!("{:.2} s", value / 1000.0),
+            Format::Ms => format!("{:.0} ms", value),
+            Format::KiB => format!("{:.0} KiB", value / 1024.0),
+            Format::Integer => format!("{:.0}", value),
+            Format::Decimal => format!("{:.3}", value),
+            Format::Score => format!("{:.1}", value),
+        }
+    }
+}
+
+/// Why a Lighthouse report's metrics couldn't be read.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricsError {
+    /// A top-level object every report has, such as `audits`, is missing.
+    MissingSection(&'static str),
+    /// Lighthouse reported a `runtimeError` (e.g. `NO_FCP`), so the metrics are meaningless.
+    RuntimeError { code: String, message: String },
+}
+
+impl fmt::Display for MetricsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetricsError::MissingSection(section) => write!(f, "report has no `{}` object", section),
+            MetricsError::RuntimeError { code, message } => write!(f, "Lighthouse runtime error {}: {}", code, message),
+        }
+    }
+}
+
+impl Error for MetricsError {}
+
+/// Declares `LighthouseMetrics` from one table of fields and their units, and
+/// generates everything that has to visit every field, so a new field can't be
+/// silently left out of any of them. A field can pick its display [`Format`] with
+/// `as`; otherwise it gets its unit's default.
+macro_rules! lighthouse_metrics {
+    (@format $unit:ident) => { Unit::$unit.default_format() };
+    (@format $unit:ident $format:ident) => { Format::$format };
+    ($($(#[$meta:meta])* $field:ident: $unit:ident $(as $format:ident)?,)*) => {
+        #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+        pub struct LighthouseMetrics {
+            $($(#[$meta])* pub $field: f64,)*
+        }
+
+        /// Number of fields in [`LighthouseMetrics`].
+        pub const FIELD_COUNT: usize = [$(stringify!($field)),*].len();
+
+        impl LighthouseMetrics {
+            /// Every field name with its unit, in declaration order.
+            pub const FIELDS: [(&'static str, Unit); FIELD_COUNT] = [$((stringify!($field), Unit::$unit)),*];
+
+            /// Every field name, in declaration order.
+            pub const FIELD_NAMES: [&'static str; FIELD_COUNT] = [$(stringify!($field)),*];
+
+            /// The unit of the field called `field`, if there is one.
+            pub fn unit(field: &str) -> Option<Unit> {
+                Self::FIELDS.iter().find(|(name, _)| *name == field).map(|(_, unit)| *unit)
+            }
+
+            /// How the field called `field` is displayed, if there is one.
+            pub fn format_of(field: &str) -> Option<Format> {
+                match field {
+                    $(stringify!($field) => Some(lighthouse_metrics!(@format $unit $($format)?)),)*
+                    _ => None,
+                }
+            }
+
+            pub fn add(&mut self, other: &Self) {
+                $(self.$field += other.$field;)*
+            }
+
+            pub fn average(&mut self, count: f64) {
+                $(self.$field /= count;)*
+            }
+
+            /// Converts every [`Unit::Millis`] field to seconds.
+            pub fn to_seconds(&self) -> Self {
+                let mut clone = self.clone();
+                $(if Unit::$unit == Unit::Millis {
+                    clone.$field /= 1000.0;
+                })*
+                clone
+            }
+
+            /// Combines `runs` field by field: `combine` gets one field's value from every run.
+            fn combine_fields(runs: &[Self], combine: impl Fn(Vec<f64>) -> f64) -> Self {
+                Self { $($field: combine(runs.iter().map(|run| run.$field).collect()),)* }
+            }
+
+            /// Every field by name, for exporters that write one value per metric.
+            pub fn as_map(&self) -> BTreeMap<&'static str, f64> {
+                BTreeMap::from([$((stringify!($field), self.$field)),*])
+            }
+
+            /// The inverse of [`as_map`](Self::as_map): builds metrics from one value per field
+            /// name, e.g. read back from an exporter's columns.
+            pub fn try_from_fields<E>(mut value: impl FnMut(&'static str) -> Result<f64, E>) -> Result<Self, E> {
+                Ok(Self { $($field: value(stringify!($field))?,)* })
+            }
+        }
+    };
+}
+
+lighthouse_metrics! {
+    first_contentful_paint: Millis,
+    largest_contentful_paint: Millis,
+    time_to_interactive: Millis,
+    total_blocking_time: Millis as Ms,
+    cumulative_layout_shift: Unitless,
+    speed_index: Millis,
+    performance_score: Score,
+    first_meaningful_paint: Millis,
+    first_cpu_idle: Millis,
+    max_potential_fid: Millis as Ms,
+    estimated_input_latency: Millis as Ms,
+    /// Interaction to Next Paint; only measured in timespan/user-flow reports, so 0.0
+    /// (and absent from older summaries) when the report has no such audit.
+    #[serde(default)]
+    interaction_to_next_paint: Millis as Ms,
+    server_response_time: Millis as Ms,
+    javascript_bootup_time: Millis,
+    total_byte_weight: Bytes,
+    render_blocking_resources: SavingsMillis,
+    unused_javascript: SavingsMillis,
+    unused_css: SavingsMillis,
+    dom_size: Count,
+    preconnect_origins: SavingsMillis,
+    properly_sized_images: SavingsMillis,
+    efficiently_encoded_images: SavingsMillis,
+    minimize_main_thread_work: Millis,
+    minimize_render_blocking_stylesheets: Millis,
+    avoid_large_layout_shifts: Unitless,
+    accessibility_score: Score,
+    seo_score: Score,
+    best_practices_score: Score,
 }
 
 impl LighthouseMetrics {
-    pub fn add(&mut self, other: &Self) {
-        macro_rules! add_field {
-            ($field:ident) => {
-                self.$field += other.$field;
-            };
-        }
-        add_field!(first_contentful_paint);
-        add_field!(largest_contentful_paint);
-        add_field!(time_to_interactive);
-        add_field!(total_blocking_time);
-        add_field!(cumulative_layout_shift);
-        add_field!(speed_index);
-        add_field!(performance_score);
-        add_field!(first_meaningful_paint);
-        add_field!(first_cpu_idle);
-        add_field!(max_potential_fid);
-        add_field!(estimated_input_latency);
-        add_field!(server_response_time);
-        add_field!(javascript_bootup_time);
-        add_field!(total_byte_weight);
-        add_field!(render_blocking_resources);
-        add_field!(unused_javascript);
-        add_field!(unused_css);
-        add_field!(dom_size);
-        add_field!(preconnect_origins);
-        add_field!(properly_sized_images);
-        add_field!(efficiently_encoded_images);
-        add_field!(minimize_main_thread_work);
-        add_field!(minimize_render_blocking_stylesheets);
-        add_field!(avoid_large_layout_shifts);
-    }
-
-    pub fn average(&mut self, count: f64) {
-        macro_rules! div_field {
-            ($field:ident) => {
-                self.$field /= count;
-            };
-        }
-        div_field!(first_contentful_paint);
-        div_field!(largest_contentful_paint);
-        div_field!(time_to_interactive);
-        div_field!(total_blocking_time);
-        div_field!(cumulative_layout_shift);
-        div_field!(speed_index);
-        div_field!(performance_score);
-        div_field!(first_meaningful_paint);
-        div_field!(first_cpu_idle);
-        div_field!(max_potential_fid);
-        div_field!(estimated_input_latency);
-        div_field!(server_response_time);
-        div_field!(javascript_bootup_time);
-        div_field!(total_byte_weight);
-        div_field!(render_blocking_resources);
-        div_field!(unused_javascript);
-        div_field!(unused_css);
-        div_field!(dom_size);
-        div_field!(preconnect_origins);
-        div_field!(properly_sized_images);
-        div_field!(efficiently_encoded_images);
-        div_field!(minimize_main_thread_work);
-        div_field!(minimize_render_blocking_stylesheets);
-        div_field!(avoid_large_layout_shifts);
-    }
-
-    pub fn to_seconds(&self) -> Self {
-        let mut clone = self.clone();
-        macro_rules! to_sec {
-            ($field:ident) => {
-                clone.$field /= 1000.0;
-            };
-        }
-        to_sec!(first_contentful_paint);
-        to_sec!(largest_contentful_paint);
-        to_sec!(time_to_interactive);
-        to_sec!(total_blocking_time);
-        to_sec!(speed_index);
-        to_sec!(first_meaningful_paint);
-        to_sec!(first_cpu_idle);
-        to_sec!(max_potential_fid);
-        to_sec!(estimated_input_latency);
-        to_sec!(server_response_time);
-        to_sec!(javascript_bootup_time);
-        to_sec!(minimize_main_thread_work);
-        to_sec!(minimize_render_blocking_stylesheets);
-        clone
+    /// Parses performance metrics from a Lighthouse report, reading each metric from its
+    /// default audit.
+    pub fn from_json(json: &Value) -> Result<Self, MetricsError> {
+        Self::from_json_with(json, &AuditMap::default())
+    }
+
+    /// Parses performance metrics from a Lighthouse report, reading each metric from the
+    /// audit `audits` maps it to.
+    ///
+    /// Fails if the report has no `audits` or `categories` object, or if Lighthouse
+    /// reported a top-level `runtimeError`. Fields backed by an errored audit, or by a
+    /// category whose score is null, are set to NaN rather than 0.0 so they can't pass for
+    /// a real result; fields whose audit or category is missing are 0.0, and are listed in
+    /// a single warning.
+    pub fn from_json_with(json: &Value, audits: &AuditMap) -> Result<Self, MetricsError> {
+        if let Some(code) = json["runtimeError"]["code"].as_str().filter(|code| *code != "NO_ERROR") {
+            return Err(MetricsError::RuntimeError {
+                code: code.to_string(),
+                message: json["runtimeError"]["message"].as_str().unwrap_or_default().to_string(),
+            });
+        }
+
+        let report_audits = json["audits"].as_object().ok_or(MetricsError::MissingSection("audits"))?;
+        let categories = json["categories"].as_object().ok_or(MetricsError::MissingSection("categories"))?;
+        for (id, message) in errored_audits(report_audits) {
+            warn!("⚠️ Audit '{}' errored: {}", id, message);
+        }
+
+        let mut missing = Vec::new();
+        let mut score = |field: &str, category: &str| -> f64 {
+            match categories.get(category) {
+                Some(category) => category["score"].as_f64().map_or(f64::NAN, |score| score * 100.0),
+                None => {
+                    missing.push(format!("{} ({} category)", field, category));
+                    0.0
+                }
+            }
+        };
+        let performance_score = score("performance_score", "performance");
+        let accessibility_score = score("accessibility_score", "accessibility");
+        let seo_score = score("seo_score", "seo");
+        let best_practices_score = score("best_practices_score", "best-practices");
+
+        let mut audit = |field: &str| -> f64 {
+            let id = audits.audit_id(field);
+            let audit = report_audits.get(id);
+            if audit.is_some_and(|audit| audit["scoreDisplayMode"].as_str() == Some("error")) {
+                return f64::NAN;
+            }
+            audit.and_then(|audit| audit["numericValue"].as_f64()).unwrap_or_else(|| {
+                missing.push(format!("{} ({})", field, id));
+                0.0
+            })
+        };
+
+        let metrics = LighthouseMetrics {
+            first_contentful_paint: audit("first_contentful_paint"),
+            largest_contentful_paint: audit("largest_contentful_paint"),
+            time_to_interactive: audit("time_to_interactive"),
+            total_blocking_time: audit("total_blocking_time"),
+            cumulative_layout_shift: audit("cumulative_layout_shift"),
+            speed_index: audit("speed_index"),
+            performance_score,
+            first_meaningful_paint: audit("first_meaningful_paint"),
+            first_cpu_idle: audit("first_cpu_idle"),
+            max_potential_fid: audit("max_potential_fid"),
+            estimated_input_latency: audit("estimated_input_latency"),
+            interaction_to_next_paint: audit("interaction_to_next_paint"),
+            server_response_time: audit("server_response_time"),
+            javascript_bootup_time: audit("javascript_bootup_time"),
+            total_byte_weight: audit("total_byte_weight"),
+            render_blocking_resources: audit("render_blocking_resources"),
+            unused_javascript: audit("unused_javascript"),
+            unused_css: audit("unused_css"),
+            dom_size: audit("dom_size"),
+            preconnect_origins: audit("preconnect_origins"),
+            properly_sized_images: audit("properly_sized_images"),
+            efficiently_encoded_images: audit("efficiently_encoded_images"),
+            minimize_main_thread_work: audit("minimize_main_thread_work"),
+            minimize_render_blocking_stylesheets: audit("minimize_render_blocking_stylesheets"),
+            avoid_large_layout_shifts: audit("avoid_large_layout_shifts"),
+            accessibility_score,
+            seo_score,
+            best_practices_score,
+        };
+
+        if !missing.is_empty() {
+            warn!("⚠️ Missing from the report, recorded as 0: {}", missing.join(", "));
+        }
+        Ok(metrics)
     }
 
+    /// Combines several runs of the same scenario into one set of metrics.
+    ///
+    /// Returns `None` when `runs` is empty.
+    pub fn aggregate(runs: &[LighthouseMetrics], aggregation: Aggregation) -> Option<Self> {
+        let first = runs.first()?;
+        let by_score = |a: &&LighthouseMetrics, b: &&LighthouseMetrics| a.performance_score.total_cmp(&b.performance_score);
+        let scored = runs.iter().filter(|m| !m.performance_score.is_nan());
+
+        let aggregated = match aggregation {
+            Aggregation::Mean => {
+                let mut total = LighthouseMetrics::default();
+                for run in runs {
+                    total.add(run);
+                }
+                total.average(runs.len() as f64);
+                total
+            }
+            Aggregation::Median => Self::median(runs),
+            Aggregation::Best => scored.max_by(by_score).unwrap_or(first).clone(),
+            Aggregation::Worst => scored.min_by(by_score).unwrap_or(first).clone(),
+        };
+        Some(aggregated)
+    }
+
+    /// Per-field median, ignoring NaN values from errored audits. A field that errored in
+    /// every run stays NaN.
+    fn median(runs: &[LighthouseMetrics]) -> Self {
+        Self::combine_fields(runs, |mut samples| {
+            samples.retain(|x| !x.is_nan());
+            samples.sort_by(f64::total_cmp);
+            match samples.len() {
+                0 => f64::NAN,
+                n if n % 2 == 1 => samples[n / 2],
+                n => (samples[n / 2 - 1] + samples[n / 2]) / 2.0,
+            }
+        })
+    }
+
+    /// Formats `value` of the field called `field`, as held in metrics converted with
+    /// [`Self::to_seconds`], in the field's display unit: `LCP 3.30 s`, `TBT 150 ms`,
+    /// `CLS 0.083`. NaN (an errored audit) reads `n/a`.
+    pub fn format_field(field: &str, value: f64) -> String {
+        let Some(unit) = Self::unit(field) else {
+            return value.to_string();
+        };
+        if value.is_nan() {
+            return "n/a".to_string();
+        }
+        // Back to Lighthouse's milliseconds, which every Format expects for timings.
+        let raw = if unit == Unit::Millis { value * 1000.0 } else { value };
+        Self::format_of(field).unwrap_or(unit.default_format()).apply(raw)
+    }
+
+    /// Human-readable summary of seconds-based metrics; INP is only listed when the
+    /// report measured it.
     pub fn evaluate(&self) -> String {
-        format!(
-            "Performance Score: {:.2}\nFCP: {:.2}s\nLCP: {:.2}s\nTTI: {:.2}s\nTBT: {:.2}s",
-            self.performance_score,
-            self.first_contentful_paint,
-            self.largest_contentful_paint,
-            self.time_to_interactive,
-            self.total_blocking_time
-        )
+        let f = Self::format_field;
+        let mut summary = format!(
+            "Performance Score: {}\nAccessibility Score: {}\nSEO Score: {}\nBest Practices Score: {}\nFCP: {}\nSI: {}\nLCP: {}\nTTI: {}\nTBT: {}\nCLS: {}",
+            f("performance_score", self.performance_score),
+            f("accessibility_score", self.accessibility_score),
+            f("seo_score", self.seo_score),
+            f("best_practices_score", self.best_practices_score),
+            f("first_contentful_paint", self.first_contentful_paint),
+            f("speed_index", self.speed_index),
+            f("largest_contentful_paint", self.largest_contentful_paint),
+            f("time_to_interactive", self.time_to_interactive),
+            f("total_blocking_time", self.total_blocking_time),
+            f("cumulative_layout_shift", self.cumulative_layout_shift)
+        );
+        if self.has_inp() {
+            summary.push_str(&format!("\nINP: {}", f("interaction_to_next_paint", self.interaction_to_next_paint)));
+        }
+        summary
+    }
+
+    /// Whether these look like a run that silently failed rather than a real measurement:
+    /// a zero performance score with no first paint.
+    pub fn is_suspicious(&self) -> bool {
+        self.performance_score == 0.0 && self.first_contentful_paint == 0.0
+    }
+
+    /// Whether the report measured INP; navigation reports leave it at 0.0.
+    pub fn has_inp(&self) -> bool {
+        self.interaction_to_next_paint > 0.0
     }
 
+    /// The bottleneck metrics with their raw values, worst first.
+    ///
+    /// Expects metrics converted with [`Self::to_seconds`]. Metrics are ranked by how far
+    /// they are past their "good" threshold rather than by raw magnitude, since they're in
+    /// different units. NaN values (errored audits) sort last.
     pub fn top_offenders(&self) -> Vec<(&'static str, f64)> {
-        let mut offenders = vec![
-            ("TBT", self.total_blocking_time),
-            ("TTI", self.time_to_interactive),
-            ("JS Bootup", self.javascript_bootup_time),
-            ("DOM Size", self.dom_size),
-            ("Byte Weight", self.total_byte_weight),
+        // (name, value, "good" threshold in the same unit)
+        let mut offenders = [
+            ("TBT", self.total_blocking_time, 0.2),
+            ("TTI", self.time_to_interactive, 3.8),
+            ("JS Bootup", self.javascript_bootup_time, 2.0),
+            ("DOM Size", self.dom_size, 800.0),
+            ("Byte Weight", self.total_byte_weight, 1_638_400.0),
+        ];
+        let severity = |value: f64, threshold: f64| {
+            if value.is_nan() {
+                f64::NEG_INFINITY
+            } else {
+                value / threshold
+            }
+        };
+        offenders.sort_by(|a, b| severity(b.1, b.2).total_cmp(&severity(a.1, a.2)));
+        offenders.iter().map(|&(name, value, _)| (name, value)).collect()
+    }
+
+    /// Core Web Vitals ratings using the official thresholds. TBT is the lab proxy for
+    /// INP; INP itself is rated as well when the report measured it.
+    pub fn classify(&self) -> Vec<(&'static str, Rating)> {
+        self.classify_with(&CwvThresholds::default())
+    }
+
+    /// Like [`Self::classify`], with custom thresholds. Expects metrics converted with
+    /// [`Self::to_seconds`].
+    pub fn classify_with(&self, thresholds: &CwvThresholds) -> Vec<(&'static str, Rating)> {
+        vec![
+            ("LCP", thresholds.largest_contentful_paint.rate(self.largest_contentful_paint)),
+            ("CLS", thresholds.cumulative_layout_shift.rate(self.cumulative_layout_shift)),
+            ("TBT", thresholds.total_blocking_time.rate(self.total_blocking_time)),
+        ]
+        .into_iter()
+        .chain(self.has_inp().then(|| ("INP", thresholds.interaction_to_next_paint.rate(self.interaction_to_next_paint))))
+        .collect()
+    }
+
+    /// A 0–100 score computed from the raw metrics with Lighthouse's log-normal scoring
+    /// curves, so runs stay comparable even when `performance_score` is missing.
+    ///
+    /// Expects metrics converted with [`Self::to_seconds`]. Metrics that are NaN (errored
+    /// audits) are left out and the remaining weights rescaled; returns NaN if none remain.
+    pub fn composite_score(&self, weights: &Weights) -> f64 {
+        let scored = [
+            (self.first_contentful_paint, &weights.first_contentful_paint),
+            (self.speed_index, &weights.speed_index),
+            (self.largest_contentful_paint, &weights.largest_contentful_paint),
+            (self.total_blocking_time, &weights.total_blocking_time),
+            (self.cumulative_layout_shift, &weights.cumulative_layout_shift),
         ];
-        offenders.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-        offenders
-    }
-}
-
-pub async fn fetch_lighthouse_metrics(label: &str, url: &str, blocked: &[&str]) -> Result<LighthouseMetrics, Box<dyn Error>> {
-    let mut args = vec![
-        url,
-        "--output=json",
-        "--output-path=stdout",
-        "--quiet",
-        "--preset=desktop",
-        "--only-categories=performance",
-        "--save-assets",
-        "--headless",
-    ];
-
-    for pattern in blocked {
-        args.push("--blocked-url-patterns");
-        args.push(pattern);
-    }
-
-    let output = Command::new("lighthouse").args(&args).output()?;
-    if !output.status.success() {
-        return Err(format!("Lighthouse failed: {}", output.status).into());
-    }
-
-    let stdout = String::from_utf8(output.stdout)?;
-    let json: Value = serde_json::from_str(&stdout)?;
-
-    let filename = format!("lighthouse_report_{}_{}.json", label, Local::now().format("%Y-%m-%d"));
-    File::create(&filename).await?.write_all(to_string_pretty(&json)?.as_bytes()).await?;
-
-    let metrics = LighthouseMetrics {
-        first_contentful_paint: json["audits"]["first-contentful-paint"]["numericValue"].as_f64().unwrap_or(0.0),
-        largest_contentful_paint: json["audits"]["largest-contentful-paint"]["numericValue"].as_f64().unwrap_or(0.0),
-        time_to_interactive: json["audits"]["interactive"]["numericValue"].as_f64().unwrap_or(0.0),
-        total_blocking_time: json["audits"]["total-blocking-time"]["numericValue"].as_f64().unwrap_or(0.0),
-        cumulative_layout_shift: json["audits"]["cumulative-layout-shift"]["numericValue"].as_f64().unwrap_or(0.0),
-        speed_index: json["audits"]["speed-index"]["numericValue"].as_f64().unwrap_or(0.0),
-        performance_score: json["categories"]["performance"]["score"].as_f64().unwrap_or(0.0) * 100.0,
-        first_meaningful_paint: json["audits"]["first-meaningful-paint"]["numericValue"].as_f64().unwrap_or(0.0),
-        first_cpu_idle: json["audits"]["first-cpu-idle"]["numericValue"].as_f64().unwrap_or(0.0),
-        max_potential_fid: json["audits"]["max-potential-fid"]["numericValue"].as_f64().unwrap_or(0.0),
-        estimated_input_latency: json["audits"]["estimated-input-latency"]["numericValue"].as_f64().unwrap_or(0.0),
-        server_response_time: json["audits"]["server-response-time"]["numericValue"].as_f64().unwrap_or(0.0),
-        javascript_bootup_time: json["audits"]["bootup-time"]["numericValue"].as_f64().unwrap_or(0.0),
-        total_byte_weight: json["audits"]["total-byte-weight"]["numericValue"].as_f64().unwrap_or(0.0),
-        render_blocking_resources: json["audits"]["render-blocking-resources"]["numericValue"].as_f64().unwrap_or(0.0),
-        unused_javascript: json["audits"]["unused-javascript"]["numericValue"].as_f64().unwrap_or(0.0),
-        unused_css: json["audits"]["unused-css"]["numericValue"].as_f64().unwrap_or(0.0),
-        dom_size: json["audits"]["dom-size"]["numericValue"].as_f64().unwrap_or(0.0),
-        preconnect_origins: json["audits"]["preconnect-to-required-origins"]["numericValue"].as_f64().unwrap_or(0.0),
-        properly_sized_images: json["audits"]["uses-responsive-images"]["numericValue"].as_f64().unwrap_or(0.0),
-        efficiently_encoded_images: json["audits"]["uses-optimized-images"]["numericValue"].as_f64().unwrap_or(0.0),
-        minimize_main_thread_work: json["audits"]["mainthread-work-breakdown"]["numericValue"].as_f64().unwrap_or(0.0),
-        minimize_render_blocking_stylesheets: json["audits"]["uses-rel-preload"]["numericValue"].as_f64().unwrap_or(0.0),
-        avoid_large_layout_shifts: json["audits"]["layout-shift-elements"]["numericValue"].as_f64().unwrap_or(0.0),
-    };
 
-    Ok(metrics)
+        let mut total = 0.0;
+        let mut total_weight = 0.0;
+        for (value, curve) in scored {
+            if value.is_nan() || curve.weight <= 0.0 {
+                continue;
+            }
+            total += curve.score(value) * curve.weight;
+            total_weight += curve.weight;
+        }
+
+        if total_weight == 0.0 {
+            f64::NAN
+        } else {
+            total / total_weight * 100.0
+        }
+    }
+}
+
+/// A Core Web Vitals rating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rating {
+    Good,
+    NeedsImprovement,
+    Poor,
+}
+
+impl fmt::Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rating::Good => write!(f, "good"),
+            Rating::NeedsImprovement => write!(f, "needs improvement"),
+            Rating::Poor => write!(f, "poor"),
+        }
+    }
+}
+
+/// Upper bounds of the "good" and "needs improvement" bands for one metric.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct RatingThresholds {
+    pub good: f64,
+    pub poor: f64,
+}
+
+impl RatingThresholds {
+    /// Rates `value`; NaN (an errored audit) counts as poor.
+    pub fn rate(&self, value: f64) -> Rating {
+        if value <= self.good {
+            Rating::Good
+        } else if value <= self.poor {
+            Rating::NeedsImprovement
+        } else {
+            Rating::Poor
+        }
+    }
+}
+
+/// Thresholds used by [`LighthouseMetrics::classify_with`], in seconds for timings.
+///
+/// Defaults are the official Core Web Vitals thresholds, with Lighthouse's TBT
+/// thresholds as the lab proxy for INP.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CwvThresholds {
+    pub largest_contentful_paint: RatingThresholds,
+    pub cumulative_layout_shift: RatingThresholds,
+    pub total_blocking_time: RatingThresholds,
+    pub interaction_to_next_paint: RatingThresholds,
+}
+
+impl Default for CwvThresholds {
+    fn default() -> Self {
+        CwvThresholds {
+            largest_contentful_paint: RatingThresholds { good: 2.5, poor: 4.0 },
+            cumulative_layout_shift: RatingThresholds { good: 0.1, poor: 0.25 },
+            total_blocking_time: RatingThresholds { good: 0.2, poor: 0.6 },
+            interaction_to_next_paint: RatingThresholds { good: 0.2, poor: 0.5 },
+        }
+    }
+}
+
+/// How one metric contributes to [`LighthouseMetrics::composite_score`].
+///
+/// `p10` and `median` are the values that score 0.9 and 0.5 on Lighthouse's
+/// log-normal curve, in the same units as the seconds-converted metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct MetricCurve {
+    pub weight: f64,
+    pub p10: f64,
+    pub median: f64,
+}
+
+impl MetricCurve {
+    /// Scores `value` between 0 and 1, mirroring Lighthouse's `computeLogNormalScore`.
+    pub fn score(&self, value: f64) -> f64 {
+        const INVERSE_ERFC_ONE_FIFTH: f64 = 0.906_193_802_436_823_2;
+
+        if value <= 0.0 {
+            return 1.0;
+        }
+        let x_log_ratio = (value / self.median).max(f64::MIN_POSITIVE).ln();
+        let p10_log_ratio = -(self.p10 / self.median).max(f64::MIN_POSITIVE).ln();
+        let standardized_x = x_log_ratio * INVERSE_ERFC_ONE_FIFTH / p10_log_ratio;
+        let percentile = (1.0 - erf(standardized_x)) / 2.0;
+
+        // Clamp so a value never lands in a different rating band than its thresholds imply.
+        if value <= self.p10 {
+            percentile.clamp(0.9, 1.0)
+        } else if value <= self.median {
+            percentile.clamp(0.5, 0.899_999_999_999_999_9)
+        } else {
+            percentile.clamp(0.0, 0.499_999_999_999_999_94)
+        }
+    }
+}
+
+/// Per-metric weights and scoring thresholds for [`LighthouseMetrics::composite_score`].
+///
+/// Defaults match Lighthouse v10's desktop performance score.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Weights {
+    pub first_contentful_paint: MetricCurve,
+    pub speed_index: MetricCurve,
+    pub largest_contentful_paint: MetricCurve,
+    pub total_blocking_time: MetricCurve,
+    pub cumulative_layout_shift: MetricCurve,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            first_contentful_paint: MetricCurve { weight: 0.10, p10: 0.934, median: 1.6 },
+            speed_index: MetricCurve { weight: 0.10, p10: 1.311, median: 2.3 },
+            largest_contentful_paint: MetricCurve { weight: 0.25, p10: 1.2, median: 2.4 },
+            total_blocking_time: MetricCurve { weight: 0.30, p10: 0.15, median: 0.35 },
+            cumulative_layout_shift: MetricCurve { weight: 0.25, p10: 0.1, median: 0.25 },
+        }
+    }
+}
+
+/// Abramowitz–Stegun approximation of the error function, as used by Lighthouse.
+fn erf(x: f64) -> f64 {
+    const A1: f64 = 0.254_829_592;
+    const A2: f64 = -0.284_496_736;
+    const A3: f64 = 1.421_413_741;
+    const A4: f64 = -1.453_152_027;
+    const A5: f64 = 1.061_405_429;
+    const P: f64 = 0.327_591_1;
+
+    let sign = x.signum();
+    let x = x.abs();
+    let t = 1.0 / (1.0 + P * x);
+    let y = t * (A1 + t * (A2 + t * (A3 + t * (A4 + t * A5))));
+    sign * (1.0 - y * (-x * x).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_offenders_sorts_nan_last_without_panicking() {
+        let metrics = LighthouseMetrics {
+            total_blocking_time: f64::NAN,
+            time_to_interactive: 9.5,
+            javascript_bootup_time: 1.5,
+            dom_size: 1600.0,
+            total_byte_weight: 819_200.0,
+            ..Default::default()
+        };
+
+        let names: Vec<&str> = metrics.top_offenders().iter().map(|(name, _)| *name).collect();
+
+        assert_eq!(names, ["TTI", "DOM Size", "JS Bootup", "Byte Weight", "TBT"]);
+    }
+
+    #[test]
+    fn median_keeps_nan_for_audits_that_errored_in_every_run() {
+        let run = |score: f64| LighthouseMetrics {
+            performance_score: score,
+            largest_contentful_paint: f64::NAN,
+            total_blocking_time: if score > 0.5 { f64::NAN } else { 200.0 },
+            ..Default::default()
+        };
+        let runs = [run(0.4), run(0.9), run(0.6)];
+
+        let median = LighthouseMetrics::aggregate(&runs, Aggregation::Median).unwrap();
+
+        assert_eq!(median.performance_score, 0.6);
+        assert!(median.largest_contentful_paint.is_nan());
+        assert_eq!(median.total_blocking_time, 200.0);
+    }
+
+    #[test]
+    fn from_json_rejects_structurally_invalid_reports() {
+        let report = serde_json::json!({
+            "categories": { "performance": { "score": 0.72 } },
+            "audits": { "largest-contentful-paint": { "numericValue": 2400.0 } },
+        });
+        let metrics = LighthouseMetrics::from_json(&report).unwrap();
+        assert_eq!(metrics.performance_score, 72.0);
+        assert_eq!(metrics.largest_contentful_paint, 2400.0);
+
+        let no_categories = serde_json::json!({ "audits": {} });
+        assert_eq!(LighthouseMetrics::from_json(&no_categories).err(), Some(MetricsError::MissingSection("categories")));
+        assert_eq!(LighthouseMetrics::from_json(&Value::Null).err(), Some(MetricsError::MissingSection("audits")));
+        let failed = serde_json::json!({ "runtimeError": { "code": "NO_FCP", "message": "no paint" } });
+        assert!(matches!(LighthouseMetrics::from_json(&failed), Err(MetricsError::RuntimeError { .. })));
+    }
+
+    #[test]
+    fn null_category_scores_are_nan_not_zero() {
+        let report = serde_json::json!({
+            "categories": { "performance": { "score": null }, "seo": { "score": 0.9 } },
+            "audits": {},
+        });
+        let metrics = LighthouseMetrics::from_json(&report).unwrap();
+
+        assert!(metrics.performance_score.is_nan());
+        assert_eq!(metrics.seo_score, 90.0);
+        // Categories the run didn't include are missing, and warned about, not errored.
+        assert_eq!(metrics.accessibility_score, 0.0);
+    }
+
+    #[test]
+    fn fields_are_formatted_in_their_natural_unit() {
+        let format = LighthouseMetrics::format_field;
+        assert_eq!(format("largest_contentful_paint", 3.3), "3.30 s");
+        assert_eq!(format("total_blocking_time", 0.15), "150 ms");
+        assert_eq!(format("cumulative_layout_shift", 0.083), "0.083");
+        assert_eq!(format("total_byte_weight", 831_488.0), "812 KiB");
+        assert_eq!(format("performance_score", 87.5), "87.5");
+        assert_eq!(format("speed_index", f64::NAN), "n/a");
+
+        let evaluated = LighthouseMetrics {
+            total_blocking_time: 0.15,
+            speed_index: 2.1,
+            cumulative_layout_shift: 0.083,
+            ..Default::default()
+        }
+        .evaluate();
+        assert!(evaluated.contains("TBT: 150 ms"), "{}", evaluated);
+        assert!(evaluated.contains("SI: 2.10 s"), "{}", evaluated);
+        assert!(evaluated.contains("CLS: 0.083"), "{}", evaluated);
+    }
+
+    #[test]
+    fn as_map_has_one_entry_per_field() {
+        let serialized = serde_json::to_value(LighthouseMetrics::default()).unwrap();
+        let fields: Vec<&str> = serialized.as_object().unwrap().keys().map(String::as_str).collect();
+
+        let map = LighthouseMetrics::default().as_map();
+
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), fields);
+    }
+
+    #[test]
+    fn generated_methods_cover_every_field() {
+        // A distinct value per field, set through serde so the check doesn't rely on the field table.
+        let serialized = serde_json::to_value(LighthouseMetrics::default()).unwrap();
+        let fields: Vec<String> = serialized.as_object().unwrap().keys().cloned().collect();
+        let values = fields.iter().enumerate().map(|(i, field)| (field.clone(), Value::from((i + 1) as f64)));
+        let metrics: LighthouseMetrics = serde_json::from_value(Value::Object(values.collect())).unwrap();
+        let read = |m: &LighthouseMetrics, field: &str| serde_json::to_value(m).unwrap()[field].as_f64().unwrap();
+
+        let mut added = metrics.clone();
+        added.add(&metrics);
+        let mut averaged = metrics.clone();
+        averaged.average(2.0);
+        let seconds = metrics.to_seconds();
+        let map = metrics.as_map();
+
+        assert_eq!(LighthouseMetrics::FIELD_NAMES.len(), fields.len());
+        for field in &fields {
+            let value = read(&metrics, field);
+            let unit = LighthouseMetrics::unit(field);
+            assert!(unit.is_some(), "{} has no unit", field);
+            assert!(LighthouseMetrics::format_of(field).is_some(), "{} has no format", field);
+            assert_eq!(read(&added, field), value * 2.0, "add skips {}", field);
+            assert_eq!(read(&averaged, field), value / 2.0, "average skips {}", field);
+            let in_seconds = if unit == Some(Unit::Millis) { value / 1000.0 } else { value };
+            assert_eq!(read(&seconds, field), in_seconds, "to_seconds mishandles {}", field);
+            assert_eq!(map.get(field.as_str()), Some(&value), "as_map skips {}", field);
+        }
+    }
 }
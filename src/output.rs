@@ -0,0 +1,103 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use env_logger::{Builder, Env, Target};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::LevelFilter;
+
+static STATUS_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// The progress bar currently drawn on stderr, if any; log lines are printed above it.
+static PROGRESS: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Sends [`status!`](crate::status) output to stderr, keeping stdout free for
+/// machine-readable output such as `--format json`.
+pub fn status_to_stderr(enabled: bool) {
+    STATUS_TO_STDERR.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether [`status!`](crate::status) currently writes to stderr.
+pub fn is_status_to_stderr() -> bool {
+    STATUS_TO_STDERR.load(Ordering::Relaxed)
+}
+
+/// Whether [`status!`](crate::status) output may be colored: the stream it goes to
+/// is a terminal and `NO_COLOR` isn't set.
+pub fn use_color() -> bool {
+    let is_terminal = if is_status_to_stderr() {
+        io::stderr().is_terminal()
+    } else {
+        io::stdout().is_terminal()
+    };
+    is_terminal && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// Sets up `log` output on stderr, filtered by `RUST_LOG` (default `info`).
+///
+/// `quiet` drops everything below `error`, leaving only the output printed
+/// through [`status!`](crate::status), such as the final summary table.
+pub fn init_logging(quiet: bool) {
+    let mut builder = Builder::from_env(Env::default().default_filter_or("info"));
+    if quiet {
+        builder.filter_level(LevelFilter::Error);
+    }
+    builder
+        .target(Target::Pipe(Box::new(LogWriter)))
+        .format(|buf, record| writeln!(buf, "{:<5} {}", record.level(), record.args()))
+        .init();
+}
+
+/// Writes log records to stderr, above the progress bar while one is shown.
+struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match PROGRESS.lock().ok().and_then(|bar| bar.clone()) {
+            Some(bar) => bar.suspend(|| io::stderr().write_all(buf))?,
+            None => io::stderr().write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// Shows a progress bar over `total` Lighthouse runs on stderr. Returns a hidden bar
+/// when `enabled` is false, so callers can update it unconditionally.
+pub fn start_progress(total: u64, enabled: bool) -> ProgressBar {
+    if !enabled {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(total);
+    bar.set_style(
+        ProgressStyle::with_template("{bar:40} {pos}/{len} runs [{elapsed_precise}, ETA {eta}] {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_bar()),
+    );
+    if let Ok(mut progress) = PROGRESS.lock() {
+        *progress = Some(bar.clone());
+    }
+    bar
+}
+
+/// Removes the progress bar started by [`start_progress`].
+pub fn finish_progress() {
+    if let Some(bar) = PROGRESS.lock().ok().and_then(|mut bar| bar.take()) {
+        bar.finish_and_clear();
+    }
+}
+
+/// Prints a human-readable line: to stdout normally, or stderr once
+/// [`status_to_stderr`] is enabled.
+#[macro_export]
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::output::is_status_to_stderr() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
@@ -0,0 +1,227 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::config::OutputConfig;
+use crate::db::MetricsDb;
+use crate::lighthouse::{CriticalChains, FilmstripFrame};
+use crate::lighthouse_summary::{rank_against_baseline, write_markdown_summary, ScenarioMetrics};
+use crate::report::{save_metrics_to_html, save_metrics_to_txt, write_prometheus};
+use crate::summary::{append_to_summary_json, export_csv, SummaryEntry, ENV_SEPARATOR};
+
+/// The aggregated outcome of a full run, handed to every post-processor.
+#[derive(Debug, Clone)]
+pub struct RunResult {
+    /// The site the scenarios were run against.
+    pub url: String,
+    /// When the run finished.
+    pub fetch_time: String,
+    /// One aggregated entry per scenario (and cache pass) that had a successful run.
+    pub scenarios: Vec<SummaryEntry>,
+    /// Screenshot thumbnails from each scenario's last run, by scenario label.
+    pub filmstrips: Vec<(String, Vec<FilmstripFrame>)>,
+    /// Critical request chains from each scenario's last run, by scenario label.
+    pub critical_chains: Vec<(String, CriticalChains)>,
+}
+
+impl RunResult {
+    /// Markdown/HTML table rows, ranked by score delta against `baseline`.
+    pub fn ranked_rows(&self) -> Vec<ScenarioMetrics> {
+        let mut rows: Vec<ScenarioMetrics> = self
+            .scenarios
+            .iter()
+            .map(|entry| ScenarioMetrics::new(&entry.scenario, &entry.metrics))
+            .collect();
+        rank_against_baseline(&mut rows);
+        rows
+    }
+}
+
+/// Something that consumes a finished run, e.g. by writing a report.
+///
+/// Implement this to add custom outputs without touching the scenario loop.
+pub trait PostProcessor {
+    /// Short name used when reporting failures.
+    fn name(&self) -> &str;
+
+    fn process(&self, result: &RunResult) -> Result<(), Box<dyn Error>>;
+}
+
+/// Appends every scenario to `summary.json`.
+pub struct SummaryJsonProcessor {
+    pub output: OutputConfig,
+}
+
+impl PostProcessor for SummaryJsonProcessor {
+    fn name(&self) -> &str {
+        "summary.json"
+    }
+
+    fn process(&self, result: &RunResult) -> Result<(), Box<dyn Error>> {
+        for entry in &result.scenarios {
+            append_to_summary_json(entry, &self.output)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the daily `metrics_log_<date>.txt`.
+pub struct TxtProcessor {
+    pub output: OutputConfig,
+}
+
+impl PostProcessor for TxtProcessor {
+    fn name(&self) -> &str {
+        "txt log"
+    }
+
+    fn process(&self, result: &RunResult) -> Result<(), Box<dyn Error>> {
+        for entry in &result.scenarios {
+            save_metrics_to_txt(&entry.scenario, &entry.metrics, &entry.url, &entry.fetch_time, &entry.metadata, &self.output)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the `summary_<timestamp>.md` scenario table.
+pub struct MarkdownProcessor {
+    pub output: OutputConfig,
+}
+
+impl PostProcessor for MarkdownProcessor {
+    fn name(&self) -> &str {
+        "markdown summary"
+    }
+
+    fn process(&self, result: &RunResult) -> Result<(), Box<dyn Error>> {
+        write_markdown_summary(&result.ranked_rows(), &self.output)?;
+        Ok(())
+    }
+}
+
+/// Writes the self-contained `summary_<timestamp>.html` report.
+pub struct HtmlProcessor {
+    pub output: OutputConfig,
+}
+
+impl PostProcessor for HtmlProcessor {
+    fn name(&self) -> &str {
+        "HTML report"
+    }
+
+    fn process(&self, result: &RunResult) -> Result<(), Box<dyn Error>> {
+        save_metrics_to_html(
+            &result.ranked_rows(),
+            &result.filmstrips,
+            &result.critical_chains,
+            &result.url,
+            &result.fetch_time,
+            &self.output,
+        )
+    }
+}
+
+/// Exports the `summary.json` history as CSV. Register it after [`SummaryJsonProcessor`]
+/// so the export includes the current run.
+pub struct CsvProcessor {
+    pub out_dir: PathBuf,
+    pub path: PathBuf,
+}
+
+impl PostProcessor for CsvProcessor {
+    fn name(&self) -> &str {
+        "CSV export"
+    }
+
+    fn process(&self, _result: &RunResult) -> Result<(), Box<dyn Error>> {
+        export_csv(&self.out_dir, &self.path)
+    }
+}
+
+/// Writes one `lighthouse_<scenario>.prom` textfile per scenario for the node_exporter
+/// textfile collector; runs with an `--env-label` get `lighthouse_<scenario>@<env>.prom`
+/// and an `env` label.
+pub struct PrometheusProcessor {
+    pub out_dir: PathBuf,
+}
+
+impl PostProcessor for PrometheusProcessor {
+    fn name(&self) -> &str {
+        "Prometheus textfile"
+    }
+
+    fn process(&self, result: &RunResult) -> Result<(), Box<dyn Error>> {
+        for entry in &result.scenarios {
+            let mut labels = vec![("scenario", entry.scenario.as_str()), ("url", entry.url.as_str())];
+            let file_name = match &entry.env {
+                Some(env) => {
+                    labels.push(("env", env.as_str()));
+                    format!("lighthouse_{}{}{}.prom", entry.scenario, ENV_SEPARATOR, env)
+                }
+                None => format!("lighthouse_{}.prom", entry.scenario),
+            };
+            write_prometheus(&entry.metrics, &labels, &self.out_dir.join(file_name))?;
+        }
+        Ok(())
+    }
+}
+
+/// Records every scenario in a SQLite database.
+pub struct DbProcessor {
+    pub db: MetricsDb,
+}
+
+impl PostProcessor for DbProcessor {
+    fn name(&self) -> &str {
+        "SQLite"
+    }
+
+    fn process(&self, result: &RunResult) -> Result<(), Box<dyn Error>> {
+        for entry in &result.scenarios {
+            self.db.insert_run(&entry.scenario, &entry.url, &entry.fetch_time, &entry.metrics)?;
+        }
+        Ok(())
+    }
+}
+
+/// Registers the built-in processors enabled by `output`, plus the optional CSV export and database.
+pub fn builtin_processors(
+    output: &OutputConfig,
+    csv_path: Option<PathBuf>,
+    db: Option<MetricsDb>,
+) -> Vec<Box<dyn PostProcessor>> {
+    let mut processors: Vec<Box<dyn PostProcessor>> = Vec::new();
+
+    if output.write_json {
+        processors.push(Box::new(SummaryJsonProcessor { output: output.clone() }));
+    }
+    if output.write_txt {
+        processors.push(Box::new(TxtProcessor { output: output.clone() }));
+    }
+    if output.write_markdown {
+        processors.push(Box::new(MarkdownProcessor { output: output.clone() }));
+    }
+    if output.write_html {
+        processors.push(Box::new(HtmlProcessor { output: output.clone() }));
+    }
+    if output.write_prometheus {
+        processors.push(Box::new(PrometheusProcessor { out_dir: output.out_dir.clone() }));
+    }
+    if let Some(path) = csv_path {
+        processors.push(Box::new(CsvProcessor { out_dir: output.out_dir.clone(), path }));
+    }
+    if let Some(db) = db {
+        processors.push(Box::new(DbProcessor { db }));
+    }
+
+    processors
+}
+
+/// Runs every processor in order, stopping at the first failure.
+pub fn run_processors(processors: &[Box<dyn PostProcessor>], result: &RunResult) -> Result<(), Box<dyn Error>> {
+    for processor in processors {
+        processor
+            .process(result)
+            .map_err(|e| format!("{} post-processor failed: {}", processor.name(), e))?;
+    }
+    Ok(())
+}
@@ -1,9 +1,18 @@
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 use std::error::Error;
-use chrono::Local;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local};
+use log::info;
 
-use crate::metrics::LighthouseMetrics;
+use crate::config::OutputConfig;
+use crate::lighthouse::{CriticalChains, FilmstripFrame, RunMetadata};
+use crate::lighthouse_summary::ScenarioMetrics;
+use crate::metrics::{LighthouseMetrics, Unit};
+use crate::run_report::RunReport;
+use crate::summary::ENV_SEPARATOR;
 
 pub async fn save_metrics_to_db(metrics: &LighthouseMetrics, url: &str, time: &str) -> Result<(), Box<dyn Error>> {
     let filename = format!("metrics_log_{}.txt", Local::now().format("%Y-%m-%d"));
@@ -14,17 +23,319 @@ pub async fn save_metrics_to_db(metrics: &LighthouseMetrics, url: &str, time: &s
     Ok(())
 }
 
-/// Save a plain-text version of the metrics for human inspection.
-pub async fn save_metrics_to_txt(
+/// The `metrics_log_<date>.txt` in `out_dir` a run fetched at `fetch_time` is logged to.
+///
+/// Runs are logged by their own date, so re-parsed reports land in the log of the day they ran.
+pub fn txt_log_path(out_dir: &Path, fetch_time: &str) -> PathBuf {
+    let date = DateTime::parse_from_rfc3339(fetch_time)
+        .map(|t| t.with_timezone(&Local))
+        .unwrap_or_else(|_| Local::now())
+        .format("%Y-%m-%d");
+    out_dir.join(format!("metrics_log_{}.txt", date))
+}
+
+/// Appends a plain-text block for one scenario to the day's `metrics_log_<date>.txt`,
+/// creating the file on first write.
+pub fn save_metrics_to_txt(
+    scenario: &str,
     metrics: &LighthouseMetrics,
     url: &str,
     fetch_time: &str,
+    metadata: &RunMetadata,
+    output: &OutputConfig,
 ) -> Result<(), Box<dyn Error>> {
-    let date = Local::now().format("%Y-%m-%d").to_string();
-    let filename = format!("metrics_log_{}.txt", date);
-    let mut file = tokio::fs::File::create(filename).await?;
+    if !output.write_txt {
+        return Ok(());
+    }
+
+    let filename = txt_log_path(&output.out_dir, fetch_time);
     let summary = metrics.evaluate();
-    let content = format!("URL: {}\nFetch Time: {}\n{}\n", url, fetch_time, summary);
-    file.write_all(content.as_bytes()).await?;
+    let content = format!(
+        "=== Scenario: {} ===\nURL: {}\nFetch Time: {}\nLighthouse Version: {}\nUser Agent: {}\n{}\n\n",
+        scenario, url, fetch_time, metadata.lighthouse_version, metadata.host_user_agent, summary
+    );
+    let mut file = OpenOptions::new().create(true).append(true).open(filename)?;
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+/// Escapes a Prometheus label value.
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Writes `metrics` as a node_exporter textfile-collector `.prom` file with one
+/// `lighthouse_*` gauge per field, each carrying `labels`.
+///
+/// `metrics` should already be converted with [`LighthouseMetrics::to_seconds`]; timing
+/// fields get a `_seconds` suffix. The file is written next to `path` and renamed into
+/// place so the collector never scrapes a half-written file.
+pub fn write_prometheus(metrics: &LighthouseMetrics, labels: &[(&str, &str)], path: &Path) -> Result<(), Box<dyn Error>> {
+    let label_set = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let mut content = String::new();
+    for (field, value) in metrics.as_map() {
+        let name = if LighthouseMetrics::unit(field) == Some(Unit::Millis) {
+            format!("lighthouse_{}_seconds", field)
+        } else {
+            format!("lighthouse_{}", field)
+        };
+        content.push_str(&format!("# HELP {} Lighthouse {} for the latest run.\n", name, field.replace('_', " ")));
+        content.push_str(&format!("# TYPE {} gauge\n", name));
+        content.push_str(&format!("{}{{{}}} {}\n", name, label_set, value));
+    }
+
+    let tmp_path = path.with_extension("prom.tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Renders each scenario's screenshot thumbnails as a row of inline images.
+fn render_filmstrips(filmstrips: &[(String, Vec<FilmstripFrame>)]) -> String {
+    if filmstrips.iter().all(|(_, frames)| frames.is_empty()) {
+        return String::new();
+    }
+
+    let mut html = String::from("<h2>Filmstrip</h2>\n");
+    for (scenario, frames) in filmstrips.iter().filter(|(_, frames)| !frames.is_empty()) {
+        html.push_str(&format!("<h3>{}</h3>\n<div style=\"display: flex; gap: 4px;\">\n", escape_html(scenario)));
+        for frame in frames {
+            html.push_str(&format!(
+                "<figure style=\"margin: 0;\"><img src=\"{}\" width=\"100\" alt=\"\"><figcaption>{:.0} ms</figcaption></figure>\n",
+                escape_html(&frame.data_uri),
+                frame.timing_ms
+            ));
+        }
+        html.push_str("</div>\n");
+    }
+    html
+}
+
+/// Renders each scenario's critical request chain summary: depth and the slowest path.
+fn render_critical_chains(chains: &[(String, CriticalChains)]) -> String {
+    if chains.iter().all(|(_, chains)| chains.is_empty()) {
+        return String::new();
+    }
+
+    let mut html = String::from("<h2>Critical Request Chains</h2>\n");
+    for (scenario, chains) in chains.iter().filter(|(_, chains)| !chains.is_empty()) {
+        html.push_str(&format!(
+            "<h3>{}</h3>\n<p>Max depth: {} &middot; Longest path: {:.0} ms</p>\n<ol>\n",
+            escape_html(scenario),
+            chains.max_depth,
+            chains.longest_duration_ms
+        ));
+        for url in &chains.longest_path {
+            html.push_str(&format!("<li>{}</li>\n", escape_html(url)));
+        }
+        html.push_str("</ol>\n");
+    }
+    html
+}
+
+/// Escapes text for safe inclusion in HTML.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders an inline SVG bar chart of performance scores (0–100).
+fn render_score_chart(summary_data: &[ScenarioMetrics]) -> String {
+    const LABEL_WIDTH: f64 = 160.0;
+    const BAR_WIDTH: f64 = 400.0;
+    const ROW_HEIGHT: f64 = 28.0;
+
+    let height = ROW_HEIGHT * summary_data.len() as f64;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" role=\"img\">\n",
+        LABEL_WIDTH + BAR_WIDTH + 60.0,
+        height
+    );
+
+    for (i, s) in summary_data.iter().enumerate() {
+        let y = i as f64 * ROW_HEIGHT;
+        let width = s.perf_score.clamp(0.0, 100.0) / 100.0 * BAR_WIDTH;
+        let color = if s.perf_score >= 90.0 {
+            "#0c6"
+        } else if s.perf_score >= 50.0 {
+            "#fa3"
+        } else {
+            "#f33"
+        };
+        svg.push_str(&format!(
+            "  <text x=\"0\" y=\"{:.0}\" font-size=\"13\">{}</text>\n",
+            y + 18.0,
+            escape_html(&s.name)
+        ));
+        svg.push_str(&format!(
+            "  <rect x=\"{:.0}\" y=\"{:.0}\" width=\"{:.1}\" height=\"20\" fill=\"{}\" />\n",
+            LABEL_WIDTH,
+            y + 4.0,
+            width,
+            color
+        ));
+        svg.push_str(&format!(
+            "  <text x=\"{:.1}\" y=\"{:.0}\" font-size=\"13\">{:.1}</text>\n",
+            LABEL_WIDTH + width + 6.0,
+            y + 18.0,
+            s.perf_score
+        ));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Save a self-contained HTML report (table plus inline SVG score chart) for stakeholders.
+pub fn save_metrics_to_html(
+    summary_data: &[ScenarioMetrics],
+    filmstrips: &[(String, Vec<FilmstripFrame>)],
+    critical_chains: &[(String, CriticalChains)],
+    url: &str,
+    fetch_time: &str,
+    output: &OutputConfig,
+) -> Result<(), Box<dyn Error>> {
+    if !output.write_html {
+        return Ok(());
+    }
+
+    let format = LighthouseMetrics::format_field;
+    let mut rows = String::new();
+    for s in summary_data {
+        rows.push_str(&format!(
+            "<tr><td>{}</td><td>{:.1}</td><td>{:+.1}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape_html(&s.name),
+            s.perf_score,
+            s.delta_perf,
+            format("first_contentful_paint", s.fcp),
+            format("largest_contentful_paint", s.lcp),
+            format("time_to_interactive", s.tti),
+            format("total_blocking_time", s.tbt)
+        ));
+    }
+
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Lighthouse Performance Report</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; margin-bottom: 2em; }}
+th, td {{ border: 1px solid #ccc; padding: 4px 10px; text-align: right; }}
+th:first-child, td:first-child {{ text-align: left; }}
+</style>
+</head>
+<body>
+<h1>Lighthouse Performance Report</h1>
+<p>URL: {url}<br>Fetch Time: {fetch_time}</p>
+<table>
+<tr><th>Scenario</th><th>Perf</th><th>&Delta;Perf</th><th>FCP</th><th>LCP</th><th>TTI</th><th>TBT</th></tr>
+{rows}</table>
+<h2>Performance Score</h2>
+{chart}{chains}{filmstrips}</body>
+</html>
+"#,
+        url = escape_html(url),
+        fetch_time = escape_html(fetch_time),
+        rows = rows,
+        chart = render_score_chart(summary_data),
+        chains = render_critical_chains(critical_chains),
+        filmstrips = render_filmstrips(filmstrips),
+    );
+
+    let filename = output.out_dir.join(format!("summary_{}.html", output.timestamp()));
+    fs::write(&filename, html)?;
+    info!("HTML report written to {}", filename.display());
     Ok(())
 }
+
+/// Writes the run as a JUnit XML report: one test case per scenario and cache pass,
+/// failing when the scenario breached its budget. The failure lists the violations
+/// and the scenario's headline metrics, so they show up in CI test views.
+pub fn write_junit(report: &RunReport, path: &Path) -> Result<(), Box<dyn Error>> {
+    let failures = report.scenarios.iter().filter(|s| !s.budget_violations.is_empty()).count();
+    let mut cases = String::new();
+    for scenario in &report.scenarios {
+        let entry = &scenario.entry;
+        let m = &entry.metrics;
+        let name = match &entry.env {
+            Some(env) => format!("{}{}{}", entry.scenario, ENV_SEPARATOR, env),
+            None => entry.scenario.clone(),
+        };
+        let format = LighthouseMetrics::format_field;
+        let metrics = format!(
+            "Perf: {:.1} | FCP: {} | LCP: {} | TBT: {} | CLS: {} | SI: {}",
+            m.performance_score,
+            format("first_contentful_paint", m.first_contentful_paint),
+            format("largest_contentful_paint", m.largest_contentful_paint),
+            format("total_blocking_time", m.total_blocking_time),
+            format("cumulative_layout_shift", m.cumulative_layout_shift),
+            format("speed_index", m.speed_index)
+        );
+        cases.push_str(&format!(
+            "    <testcase classname=\"{}\" name=\"{}\">\n",
+            escape_html(&entry.url),
+            escape_html(&name)
+        ));
+        if !scenario.budget_violations.is_empty() {
+            let violations: Vec<String> = scenario.budget_violations.iter().map(|v| v.to_string()).collect();
+            cases.push_str(&format!(
+                "      <failure type=\"budget\" message=\"{}\">{}\n{}</failure>\n",
+                escape_html(&violations.join("; ")),
+                escape_html(&violations.join("\n")),
+                escape_html(&metrics)
+            ));
+        }
+        cases.push_str(&format!("      <system-out>{}</system-out>\n", escape_html(&metrics)));
+        cases.push_str("    </testcase>\n");
+    }
+
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <testsuites name=\"performance-tracker\" tests=\"{tests}\" failures=\"{failures}\">\n\
+         \x20 <testsuite name=\"lighthouse\" tests=\"{tests}\" failures=\"{failures}\" timestamp=\"{timestamp}\">\n\
+         {cases}\
+         \x20 </testsuite>\n\
+         </testsuites>\n",
+        tests = report.scenarios.len(),
+        failures = failures,
+        timestamp = Local::now().format("%Y-%m-%dT%H:%M:%S"),
+        cases = cases
+    );
+    fs::write(path, xml)?;
+    info!("JUnit report written to {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn txt_log_keeps_every_scenario() {
+        let out_dir = TempDir::new("txt");
+        let output = OutputConfig { out_dir: out_dir.to_path_buf(), ..Default::default() };
+        let metadata = RunMetadata::default();
+
+        for scenario in ["baseline", "no-tealium"] {
+            save_metrics_to_txt(scenario, &LighthouseMetrics::default(), "https://example.com", "now", &metadata, &output)
+                .unwrap();
+        }
+
+        let date = Local::now().format("%Y-%m-%d").to_string();
+        let log = fs::read_to_string(out_dir.join(format!("metrics_log_{}.txt", date))).unwrap();
+
+        assert!(log.contains("=== Scenario: baseline ==="));
+        assert!(log.contains("=== Scenario: no-tealium ==="));
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::UNIX_EPOCH;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::lighthouse::load_report;
+
+/// Audits dropped from cached reports: screenshots make up most of a report's size and
+/// nothing that reads saved reports back uses them.
+const HEAVY_AUDITS: [&str; 3] = ["full-page-screenshot", "final-screenshot", "screenshot-thumbnails"];
+
+/// The process-wide cache used by the summary and re-parse modes.
+static GLOBAL: LazyLock<ReportCache> = LazyLock::new(ReportCache::new);
+
+/// Identifies one version of a file: a cached parse is reused only while both match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Fingerprint {
+    len: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+}
+
+impl Fingerprint {
+    fn of(path: &Path) -> std::io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Ok(Fingerprint {
+            len: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+        })
+    }
+}
+
+/// A cached report as stored on disk.
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    path: PathBuf,
+    fingerprint: Fingerprint,
+    report: Value,
+}
+
+/// Parsed Lighthouse reports keyed by path, reused while the file's size and
+/// modification time are unchanged.
+///
+/// Entries live in memory for the life of the cache and, with a disk directory set,
+/// also across invocations. Cached reports omit the screenshot audits.
+#[derive(Debug, Default)]
+pub struct ReportCache {
+    memory: Mutex<HashMap<PathBuf, (Fingerprint, Arc<Value>)>>,
+    disk_dir: Mutex<Option<PathBuf>>,
+}
+
+impl ReportCache {
+    /// An empty, memory-only cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The process-wide cache.
+    pub fn global() -> &'static ReportCache {
+        &GLOBAL
+    }
+
+    /// Also keeps parsed reports in `dir`, so later invocations can skip parsing unchanged files.
+    pub fn set_disk_dir(&self, dir: PathBuf) {
+        *self.disk_dir.lock().unwrap_or_else(|e| e.into_inner()) = Some(dir);
+    }
+
+    /// Reads, parses and validates the report at `path` (see [`load_report`]), or returns
+    /// the cached parse if the file hasn't changed since.
+    pub fn load(&self, path: &Path, max_bytes: u64) -> Result<Arc<Value>, Box<dyn Error>> {
+        let fingerprint = Fingerprint::of(path)?;
+        let cached = self.memory.lock().unwrap_or_else(|e| e.into_inner()).get(path).cloned();
+        if let Some((cached_fingerprint, report)) = cached {
+            if cached_fingerprint == fingerprint {
+                return Ok(report);
+            }
+        }
+
+        let report = match self.load_from_disk(path, fingerprint) {
+            Some(report) => Arc::new(report),
+            None => {
+                let mut report = load_report(path, max_bytes)?;
+                if let Some(audits) = report["audits"].as_object_mut() {
+                    for audit in HEAVY_AUDITS {
+                        audits.remove(audit);
+                    }
+                }
+                self.save_to_disk(path, fingerprint, &report);
+                Arc::new(report)
+            }
+        };
+
+        self.memory
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(path.to_path_buf(), (fingerprint, Arc::clone(&report)));
+        Ok(report)
+    }
+
+    /// Where `path`'s entry lives in the disk cache, if one is set.
+    fn disk_path(&self, path: &Path) -> Option<PathBuf> {
+        let dir = self.disk_dir.lock().unwrap_or_else(|e| e.into_inner()).clone()?;
+        let mut hasher = DefaultHasher::new();
+        fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()).hash(&mut hasher);
+        Some(dir.join(format!("{:016x}.json", hasher.finish())))
+    }
+
+    fn load_from_disk(&self, path: &Path, fingerprint: Fingerprint) -> Option<Value> {
+        let cache_file = self.disk_path(path)?;
+        let entry: DiskEntry = serde_json::from_str(&fs::read_to_string(&cache_file).ok()?).ok()?;
+        (entry.path == path && entry.fingerprint == fingerprint).then(|| {
+            debug!("Report cache hit for {}", path.display());
+            entry.report
+        })
+    }
+
+    fn save_to_disk(&self, path: &Path, fingerprint: Fingerprint, report: &Value) {
+        let Some(cache_file) = self.disk_path(path) else {
+            return;
+        };
+        let entry = DiskEntry { path: path.to_path_buf(), fingerprint, report: report.clone() };
+        let written = cache_file
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&cache_file, serde_json::to_vec(&entry)?));
+        if let Err(e) = written {
+            warn!("⚠️ Could not write report cache {}: {}", cache_file.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn changed_reports_are_reparsed() {
+        let dir = TempDir::new("cache");
+        let path = dir.join("lighthouse_report_baseline_2024-01-01.report.json");
+        let report = |score: f64| {
+            serde_json::json!({
+                "lighthouseVersion": "12.0.0",
+                "categories": { "performance": { "score": score } },
+                "audits": { "final-screenshot": { "details": { "data": "data:image/jpeg;base64,AAAA" } } },
+            })
+            .to_string()
+        };
+
+        let cache = ReportCache::new();
+        cache.set_disk_dir(dir.join("cache"));
+        fs::write(&path, report(0.5)).unwrap();
+        let first = cache.load(&path, u64::MAX).unwrap();
+        // Different length, so the fingerprint changes even if the mtime doesn't.
+        fs::write(&path, report(0.75)).unwrap();
+        let second = cache.load(&path, u64::MAX).unwrap();
+        let from_disk = ReportCache::new();
+        from_disk.set_disk_dir(dir.join("cache"));
+        let third = from_disk.load(&path, u64::MAX).unwrap();
+
+        assert_eq!(first["categories"]["performance"]["score"], 0.5);
+        assert_eq!(second["categories"]["performance"]["score"], 0.75);
+        assert_eq!(third, second);
+        assert!(second["audits"].get("final-screenshot").is_none());
+    }
+}
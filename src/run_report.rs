@@ -0,0 +1,355 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use log::warn;
+use serde::Serialize;
+
+use crate::metrics::LighthouseMetrics;
+use crate::status;
+
+use crate::budget::{BudgetViolation, ByteUsage};
+use crate::lighthouse::{CriticalChains, FilmstripFrame, Opportunity};
+use crate::summary::{MetricDelta, RunningStats, SummaryEntry};
+use crate::trace::TraceSummary;
+
+/// Everything a full run produced, in a shape suitable for `--format json`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunReport {
+    pub scenarios: Vec<ScenarioResult>,
+    /// Lighthouse runs attempted and failed, including scenarios where every run failed.
+    pub runs: RunCounts,
+    /// Filmstrip frames per report label, for scenarios that captured any.
+    #[serde(skip)]
+    pub filmstrips: Vec<(String, Vec<FilmstripFrame>)>,
+}
+
+impl RunReport {
+    /// Appends another run's results, e.g. a later `--repeat` iteration.
+    pub fn extend(&mut self, other: RunReport) {
+        self.scenarios.extend(other.scenarios);
+        self.filmstrips.extend(other.filmstrips);
+        self.runs.add(other.runs);
+    }
+
+    /// Every successful run's LCP in seconds for `scenario`, per URL, across all iterations.
+    pub fn lcp_runs(&self, scenario: &str) -> BTreeMap<&str, Vec<f64>> {
+        let mut by_url: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+        for result in self.scenarios.iter().filter(|s| s.entry.scenario == scenario) {
+            by_url
+                .entry(result.entry.url.as_str())
+                .or_default()
+                .extend(result.runs.iter().map(|m| m.largest_contentful_paint));
+        }
+        by_url
+    }
+}
+
+/// How many measured runs were attempted and how many of them failed.
+///
+/// Cache-priming runs aren't counted; their metrics are never used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct RunCounts {
+    pub attempted: usize,
+    pub failed: usize,
+    /// Scenarios (per cache pass and URL) with at least one failed run.
+    pub scenarios_with_failures: usize,
+}
+
+impl RunCounts {
+    /// Records one scenario's runs.
+    pub fn record(&mut self, attempted: usize, succeeded: usize) {
+        let failed = attempted.saturating_sub(succeeded);
+        self.attempted += attempted;
+        self.failed += failed;
+        if failed > 0 {
+            self.scenarios_with_failures += 1;
+        }
+    }
+
+    pub fn add(&mut self, other: RunCounts) {
+        self.attempted += other.attempted;
+        self.failed += other.failed;
+        self.scenarios_with_failures += other.scenarios_with_failures;
+    }
+
+    /// Share of attempted runs that succeeded, in percent; 100 when nothing ran.
+    pub fn success_rate(&self) -> f64 {
+        if self.attempted == 0 {
+            return 100.0;
+        }
+        (self.attempted - self.failed) as f64 / self.attempted as f64 * 100.0
+    }
+}
+
+impl fmt::Display for RunCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} runs succeeded", self.attempted - self.failed, self.attempted)?;
+        if self.failed > 0 {
+            write!(
+                f,
+                " ({} failure{} across {} scenario{})",
+                self.failed,
+                if self.failed == 1 { "" } else { "s" },
+                self.scenarios_with_failures,
+                if self.scenarios_with_failures == 1 { "" } else { "s" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The aggregated outcome of one scenario and cache pass.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScenarioResult {
+    #[serde(flatten)]
+    pub entry: SummaryEntry,
+    /// Label the scenario's report files were written under.
+    #[serde(skip)]
+    pub report_label: String,
+    /// How many of the requested runs succeeded and went into the aggregate.
+    pub successful_runs: usize,
+    /// Each successful run's metrics in seconds, before aggregation.
+    #[serde(skip)]
+    pub runs: Vec<LighthouseMetrics>,
+    pub budget_violations: Vec<BudgetViolation>,
+    /// Transfer size against each byte budget, for the last successful run.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub byte_budgets: Vec<ByteUsage>,
+    pub opportunities: Vec<Opportunity>,
+    /// Render-blocking request chains from the last successful run.
+    #[serde(skip_serializing_if = "CriticalChains::is_empty")]
+    pub critical_chains: CriticalChains,
+    /// Main-thread summary of the last successful run's trace, with `--sample-trace`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<TraceSummary>,
+    /// Change since the previous recorded run, if there was one.
+    #[serde(skip)]
+    pub previous: Option<Vec<MetricDelta>>,
+}
+
+/// Resamples drawn to estimate the confidence interval in [`compare_p75`].
+const BOOTSTRAP_RESAMPLES: usize = 2000;
+
+/// Below this many runs per scenario, a bootstrap interval is too rough to trust.
+pub const MIN_P75_RUNS: usize = 5;
+
+/// How one scenario's 75th percentile of a metric compares with another's.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct P75Comparison {
+    pub base_p75: f64,
+    pub candidate_p75: f64,
+    /// 95% bootstrap confidence interval of `candidate_p75 - base_p75`.
+    pub ci_low: f64,
+    pub ci_high: f64,
+}
+
+impl P75Comparison {
+    pub fn difference(&self) -> f64 {
+        self.candidate_p75 - self.base_p75
+    }
+
+    /// Whether the difference is likely real, for a metric where lower is better.
+    pub fn verdict(&self) -> &'static str {
+        if self.ci_high < 0.0 {
+            "likely a real improvement"
+        } else if self.ci_low > 0.0 {
+            "likely a real regression"
+        } else {
+            "not distinguishable from run-to-run noise"
+        }
+    }
+}
+
+/// Compares the 75th percentiles of two sets of per-run values, with a 95% percentile
+/// bootstrap interval for their difference. NaN values (errored audits) are ignored;
+/// returns `None` when either side has no values left.
+///
+/// Resampling uses a fixed seed, so the same runs always give the same interval.
+pub fn compare_p75(base: &[f64], candidate: &[f64]) -> Option<P75Comparison> {
+    let base: Vec<f64> = base.iter().copied().filter(|v| !v.is_nan()).collect();
+    let candidate: Vec<f64> = candidate.iter().copied().filter(|v| !v.is_nan()).collect();
+    if base.is_empty() || candidate.is_empty() {
+        return None;
+    }
+
+    let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+    let mut resample = |values: &[f64]| -> f64 {
+        let drawn: Vec<f64> = (0..values.len()).map(|_| values[rng.next_index(values.len())]).collect();
+        p75(drawn)
+    };
+    let mut differences: Vec<f64> = (0..BOOTSTRAP_RESAMPLES)
+        .map(|_| resample(&candidate) - resample(&base))
+        .collect();
+    differences.sort_by(f64::total_cmp);
+
+    Some(P75Comparison {
+        base_p75: p75(base),
+        candidate_p75: p75(candidate),
+        ci_low: quantile(&differences, 0.025),
+        ci_high: quantile(&differences, 0.975),
+    })
+}
+
+fn p75(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    quantile(&values, 0.75)
+}
+
+/// The `q` quantile of sorted, non-empty `values`, interpolating between neighbours.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let rank = q * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+/// A small xorshift generator: enough for resampling, without pulling in a dependency.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next_index(&mut self, len: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % len as u64) as usize
+    }
+}
+
+/// Prints the p75 LCP comparison of `candidate` against `base`, per URL both ran against.
+pub fn print_p75_comparison(report: &RunReport, base: &str, candidate: &str) {
+    status!("\n=== p75 LCP: {} vs {} ===", candidate, base);
+    let base_runs = report.lcp_runs(base);
+    let candidate_runs = report.lcp_runs(candidate);
+    let mut compared = false;
+    for (url, base_lcp) in &base_runs {
+        let Some(candidate_lcp) = candidate_runs.get(url) else {
+            continue;
+        };
+        let Some(comparison) = compare_p75(base_lcp, candidate_lcp) else {
+            continue;
+        };
+        compared = true;
+        status!("{}", url);
+        status!(
+            "  {}: {:.2}s ({} runs) | {}: {:.2}s ({} runs)",
+            base,
+            comparison.base_p75,
+            base_lcp.len(),
+            candidate,
+            comparison.candidate_p75,
+            candidate_lcp.len()
+        );
+        status!(
+            "  Difference: {:+.2}s, 95% CI [{:+.2}s, {:+.2}s] — {}",
+            comparison.difference(),
+            comparison.ci_low,
+            comparison.ci_high,
+            comparison.verdict()
+        );
+        if base_lcp.len().min(candidate_lcp.len()) < MIN_P75_RUNS {
+            warn!(
+                "⚠️ Fewer than {} runs per scenario; use more --runs or --repeat for a trustworthy interval.",
+                MIN_P75_RUNS
+            );
+        }
+    }
+    if !compared {
+        status!("No URL has successful runs of both '{}' and '{}'.", base, candidate);
+    }
+}
+
+/// Reads one value out of seconds-based metrics.
+type MetricReader = fn(&LighthouseMetrics) -> f64;
+
+/// Metrics compared across `--repeat` iterations.
+const VARIANCE_METRICS: [(&str, MetricReader); 6] = [
+    ("Perf", |m| m.performance_score),
+    ("FCP (s)", |m| m.first_contentful_paint),
+    ("LCP (s)", |m| m.largest_contentful_paint),
+    ("TBT (s)", |m| m.total_blocking_time),
+    ("CLS", |m| m.cumulative_layout_shift),
+    ("SI (s)", |m| m.speed_index),
+];
+
+/// How much each scenario's aggregated metrics moved between repeated runs of the suite.
+#[derive(Debug, Clone, Default)]
+pub struct VarianceReport {
+    iterations: usize,
+    /// Stats per report label, in the order scenarios first ran.
+    scenarios: Vec<(String, [RunningStats; VARIANCE_METRICS.len()])>,
+}
+
+impl VarianceReport {
+    /// Adds one iteration's results.
+    pub fn add(&mut self, report: &RunReport) {
+        self.iterations += 1;
+        for scenario in &report.scenarios {
+            let index = match self.scenarios.iter().position(|(label, _)| label == &scenario.report_label) {
+                Some(index) => index,
+                None => {
+                    self.scenarios.push((scenario.report_label.clone(), Default::default()));
+                    self.scenarios.len() - 1
+                }
+            };
+            for (stats, (_, value)) in self.scenarios[index].1.iter_mut().zip(VARIANCE_METRICS) {
+                stats.push(value(&scenario.entry.metrics));
+            }
+        }
+    }
+
+    /// Prints min / mean / max / standard deviation per scenario and metric.
+    pub fn print(&self) {
+        status!("=== Variance across {} iterations ===", self.iterations);
+        status!(
+            "{:<24} {:<8} {:>9} {:>9} {:>9} {:>9} {:>4}",
+            "Scenario", "Metric", "Min", "Mean", "Max", "StdDev", "N"
+        );
+        for (label, stats) in &self.scenarios {
+            for (i, (stat, (metric, _))) in stats.iter().zip(VARIANCE_METRICS).enumerate() {
+                status!(
+                    "{:<24} {:<8} {:>9.3} {:>9.3} {:>9.3} {:>9.3} {:>4}",
+                    if i == 0 { label.as_str() } else { "" },
+                    metric,
+                    stat.min(),
+                    stat.mean(),
+                    stat.max(),
+                    stat.std_dev(),
+                    stat.count()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_counts_include_failures_per_scenario() {
+        let mut counts = RunCounts::default();
+        counts.record(6, 6);
+        counts.record(6, 4);
+        counts.record(6, 5);
+
+        assert_eq!(counts.to_string(), "15/18 runs succeeded (3 failures across 2 scenarios)");
+        assert!((counts.success_rate() - 83.33).abs() < 0.01);
+        assert_eq!(RunCounts::default().success_rate(), 100.0);
+    }
+
+    #[test]
+    fn p75_comparison_separates_real_changes_from_noise() {
+        let base = [2.4, 2.6, 2.5, 2.7, 2.5, 2.6, 2.8, 2.4];
+        let faster: Vec<f64> = base.iter().map(|lcp| lcp - 0.8).collect();
+        let similar = [2.5, 2.7, 2.4, 2.6, 2.6, 2.5, 2.7, 2.5];
+
+        let improved = compare_p75(&base, &faster).unwrap();
+        assert!((improved.difference() + 0.8).abs() < 1e-9);
+        assert!(improved.ci_high < 0.0);
+        assert_eq!(improved.verdict(), "likely a real improvement");
+
+        let noise = compare_p75(&base, &similar).unwrap();
+        assert!(noise.ci_low <= 0.0 && noise.ci_high >= 0.0);
+        assert_eq!(compare_p75(&base, &similar), Some(noise), "resampling is seeded");
+        assert_eq!(compare_p75(&[f64::NAN], &base), None);
+    }
+}
@@ -0,0 +1,1253 @@
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{Local, Utc};
+use colored::Colorize;
+use indicatif::ProgressBar;
+use log::{debug, error, info, warn};
+use tokio::sync::{mpsc, oneshot, OwnedSemaphorePermit, Semaphore};
+
+use crate::baseline::{update_moving_baseline, BaselineOutcome, PinnedBaseline};
+use crate::budget::Budgets;
+use crate::cli::{Args, OutputFormat};
+use crate::config::{
+    builtin_scenarios, check_unique_labels, select_scenarios, start_new_run, timestamp_has_minutes, validate_timestamp_format, Config, OutputConfig,
+    ScenarioConfig,
+};
+use crate::db::MetricsDb;
+use crate::flow::{print_flow_steps, run_flow_script};
+use crate::lighthouse::{
+    diff_origins, fetch_lighthouse_metrics_with_retry, load_denylist, load_report_metrics, load_url_list,
+    merge_blocked_patterns, normalize_url, preflight, slugify_url, validate_blocked_patterns, validate_extra_headers,
+    validate_proxy, wait_for_url, AuditMap, CacheState, LighthouseOptions, LighthouseRun, RetryPolicy, WarmProfile,
+};
+use crate::metrics::{Aggregation, LighthouseMetrics, Rating};
+use crate::output;
+use crate::postprocess::{builtin_processors, run_processors, RunResult};
+use crate::report::{save_metrics_to_txt, txt_log_path, write_junit};
+use crate::report_cache::ReportCache;
+use crate::run_report::{print_p75_comparison, RunReport, ScenarioResult, VarianceReport};
+use crate::status;
+use crate::summary::{
+    compare_summaries, compare_with_previous, detect_regression, diff_metrics, period_comparison, print_deltas,
+    print_diff_table, print_summary_comparison, reparse_reports, replace_summary, summarize_local_json_reports,
+    summarize_reports_since, MetricDelta, SummaryEntry, Verdict, ENV_SEPARATOR, SUMMARY_SCHEMA_VERSION,
+};
+use crate::trace::{parse_trace_json, print_trace_summary};
+#[cfg(feature = "s3")]
+use crate::upload::{run_artifacts, upload_artifacts};
+use crate::webhook::post_summary;
+
+/// The site audited when no URLs are given.
+pub const DEFAULT_URL: &str = "https://alaskaair.com";
+
+/// Everything [`run_scenarios`] needs, independent of the command line.
+///
+/// [`RunConfig::new`] fills in the same defaults the CLI uses; adjust the fields
+/// before running.
+#[derive(Debug, Clone)]
+pub struct RunConfig {
+    pub config: Config,
+    /// Pages to audit; every scenario runs against each.
+    pub urls: Vec<String>,
+    pub scenarios: Vec<ScenarioConfig>,
+    /// Patterns blocked in every scenario, on top of its own list.
+    pub denylist: Vec<String>,
+    pub budgets: Option<Budgets>,
+    /// Cache states each scenario is audited under, in order.
+    pub cache_passes: Vec<CacheState>,
+    /// Measured runs per cold pass.
+    pub runs: u32,
+    /// Measured runs per warm pass, after one priming run.
+    pub warm_runs: u32,
+    pub aggregation: Aggregation,
+    pub retry: RetryPolicy,
+    /// Settings shared by every Lighthouse invocation; `cache` and `out_dir` are set per pass.
+    pub options: LighthouseOptions,
+    /// Add the URL to report file names, so several URLs don't overwrite each other's reports.
+    pub per_url_reports: bool,
+    /// Environment the run is tagged with; see `--env-label`.
+    pub env_label: Option<String>,
+    /// Warn when a report comes from a different Lighthouse version.
+    pub require_lh_version: Option<String>,
+    /// Summarize the main thread from each scenario's saved trace.
+    pub sample_trace: bool,
+    /// Recent runs considered by trend regression detection.
+    pub trend_window: usize,
+    /// Standard deviations below the recent mean that count as a regression.
+    pub trend_sigmas: f64,
+    /// Draw a progress bar over all runs on stderr.
+    pub show_progress: bool,
+    /// Compare every scenario against this instead of its previous run.
+    pub pinned_baseline: Option<PinnedBaseline>,
+    /// Cache passes run at the same time. Above 1 the runs compete for CPU and network,
+    /// which inflates timings, so compare only results measured at the same concurrency.
+    pub concurrency: u32,
+}
+
+impl RunConfig {
+    /// Audits [`DEFAULT_URL`] with the config's scenarios (or the built-in ones), three cold runs each.
+    pub fn new(config: Config) -> Self {
+        let scenarios = if config.scenarios.is_empty() {
+            builtin_scenarios()
+        } else {
+            config.scenarios.clone()
+        };
+        let options = LighthouseOptions {
+            max_report_bytes: config.limits.max_report_bytes,
+            throttling: config.throttling.clone(),
+            ..Default::default()
+        };
+        RunConfig {
+            config,
+            urls: vec![DEFAULT_URL.to_string()],
+            scenarios,
+            denylist: Vec::new(),
+            budgets: None,
+            cache_passes: vec![CacheState::Cold],
+            runs: 3,
+            warm_runs: 3,
+            aggregation: Aggregation::default(),
+            retry: RetryPolicy::default(),
+            options,
+            per_url_reports: false,
+            env_label: None,
+            require_lh_version: None,
+            sample_trace: false,
+            trend_window: 10,
+            trend_sigmas: 2.0,
+            show_progress: false,
+            pinned_baseline: None,
+            concurrency: 1,
+        }
+    }
+
+    /// How many measured runs a cache pass gets.
+    pub fn runs_for(&self, cache: CacheState) -> u32 {
+        match cache {
+            CacheState::Cold => self.runs,
+            CacheState::Warm => self.warm_runs,
+        }
+    }
+
+    /// Total Lighthouse invocations, including warm-cache priming runs.
+    pub fn total_runs(&self) -> u64 {
+        let per_scenario: u32 = self
+            .cache_passes
+            .iter()
+            .map(|&cache| self.runs_for(cache) + u32::from(cache == CacheState::Warm))
+            .sum();
+        (self.urls.len() * self.scenarios.len()) as u64 * u64::from(per_scenario)
+    }
+
+    /// Rejects settings the runner can't report on: scenarios sharing a label, or a
+    /// cache pass with no measured runs.
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        check_unique_labels(&self.scenarios)?;
+        if let Some(cache) = self.cache_passes.iter().find(|&&cache| self.runs_for(cache) == 0) {
+            return Err(format!("the {} cache pass needs at least one run", cache.as_str()).into());
+        }
+        Ok(())
+    }
+}
+
+/// Set by [`request_shutdown`]; checked before each scenario starts.
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+/// Asks the runner to stop once the scenario in flight has finished, e.g. on Ctrl-C.
+pub fn request_shutdown() {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
+
+/// Whether [`request_shutdown`] has been called.
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN.load(Ordering::SeqCst)
+}
+
+/// One scenario's runs under one cache state against one URL: the unit the runner
+/// schedules, and the label its runs are grouped under.
+#[derive(Debug, Clone)]
+struct CachePass {
+    /// The scenario's label.
+    label: String,
+    /// `label`, with `-warm` for the warm pass.
+    run_label: String,
+    /// `run_label` plus the URL and environment where those go in report file names.
+    report_label: String,
+    url: String,
+    blocked: Vec<String>,
+    cache: CacheState,
+    /// Measured runs, after the priming run of a warm pass.
+    runs: u32,
+    retry: RetryPolicy,
+    options: LighthouseOptions,
+}
+
+impl CachePass {
+    fn new(run: &RunConfig, scenario: &ScenarioConfig, cache: CacheState, url: &str) -> Self {
+        let label = scenario.label.as_str();
+        let run_label = match cache {
+            CacheState::Cold => label.to_string(),
+            CacheState::Warm => format!("{}-warm", label),
+        };
+        let mut report_label = if run.per_url_reports {
+            format!("{}_{}", run_label, slugify_url(url))
+        } else {
+            run_label.clone()
+        };
+        if let Some(env) = &run.env_label {
+            report_label = format!("{}{}{}", report_label, ENV_SEPARATOR, env);
+        }
+        CachePass {
+            label: label.to_string(),
+            run_label,
+            report_label,
+            url: url.to_string(),
+            blocked: scenario.blocked.clone(),
+            cache,
+            runs: run.runs_for(cache),
+            retry: run.retry,
+            options: LighthouseOptions {
+                cache,
+                out_dir: run.config.output.out_dir.clone(),
+                denylist: run.denylist.clone(),
+                ..run.options.clone()
+            },
+        }
+    }
+
+    /// One Lighthouse run of this pass, with retries.
+    async fn fetch(&self) -> Result<LighthouseRun, Box<dyn Error>> {
+        fetch_lighthouse_metrics_with_retry(&self.report_label, &self.url, &self.blocked, &self.options, &self.retry).await
+    }
+}
+
+/// One measured run's outcome, sent from its pass's task to [`collect_runs`]; `run` is
+/// `None` when it failed.
+#[derive(Debug)]
+struct RunMessage {
+    /// The pass's `report_label`.
+    label: String,
+    run: Option<LighthouseRun>,
+    /// Sent with the pass's last run; see [`CollectedRuns::slot`].
+    slot: Option<OwnedSemaphorePermit>,
+}
+
+/// A cache pass's runs once all of them have reported, with their aggregate.
+#[derive(Debug, Default)]
+struct CollectedRuns {
+    attempted: usize,
+    /// Metrics of the successful runs, in the order they finished.
+    runs: Vec<LighthouseMetrics>,
+    /// The last successful run, whose report details (opportunities, chains, ...) are shown.
+    last: Option<LighthouseRun>,
+    aggregate: Option<LighthouseMetrics>,
+    /// The pass's `--concurrency` slot, freed once its result has been reported, so a
+    /// concurrency of 1 never starts the next pass while the last one is still printing.
+    slot: Option<OwnedSemaphorePermit>,
+}
+
+/// Groups the runs arriving on `inbox` by label. Once a label has as many runs as
+/// `expected` lists for it, they are aggregated with `aggregation` and sent to its
+/// channel. Returns when every sender is gone; labels still incomplete then are dropped,
+/// which their receivers see as an error.
+async fn collect_runs(
+    mut inbox: mpsc::UnboundedReceiver<RunMessage>,
+    mut expected: HashMap<String, (usize, oneshot::Sender<CollectedRuns>)>,
+    aggregation: Aggregation,
+) {
+    let mut collected: HashMap<String, CollectedRuns> = HashMap::new();
+    while let Some(RunMessage { label, run, slot }) = inbox.recv().await {
+        let pass = collected.entry(label.clone()).or_default();
+        pass.attempted += 1;
+        pass.slot = pass.slot.take().or(slot);
+        if let Some(run) = run {
+            pass.runs.push(run.metrics.clone());
+            pass.last = Some(run);
+        }
+        if expected.get(&label).is_none_or(|(count, _)| pass.attempted < *count) {
+            continue;
+        }
+        if let Some((_, done)) = expected.remove(&label) {
+            let mut pass = collected.remove(&label).unwrap_or_default();
+            pass.aggregate = LighthouseMetrics::aggregate(&pass.runs, aggregation);
+            let _ = done.send(pass);
+        }
+    }
+}
+
+/// Runs one pass's Lighthouse invocations in order, priming the cache first for a
+/// warm pass, and sends each measured run to the collector.
+async fn run_pass(
+    mut pass: CachePass,
+    slot: OwnedSemaphorePermit,
+    progress: ProgressBar,
+    messages: mpsc::UnboundedSender<RunMessage>,
+) {
+    info!("=== Running Scenario: {} ({} cache) on {} ===", pass.label, pass.cache.as_str(), pass.url);
+    progress.set_message(pass.run_label.clone());
+
+    // Deleted once the pass's last run has finished.
+    let mut _profile = None;
+    if pass.cache == CacheState::Warm {
+        match WarmProfile::create(&pass.report_label) {
+            Ok(profile) => {
+                pass.options.profile_dir = Some(profile.path().to_path_buf());
+                _profile = Some(profile);
+            }
+            Err(e) => warn!("⚠️ Could not create a Chrome profile for {}; runs won't share a cache: {}", pass.run_label, e),
+        }
+        debug!("-> Priming cache for {}", pass.label);
+        if let Err(e) = pass.fetch().await {
+            warn!("⚠️ Cache priming run failed: {}", e);
+        }
+        progress.inc(1);
+    }
+
+    let mut slot = Some(slot);
+    for i in 0..pass.runs {
+        debug!("-> Run {}/{} for {}", i + 1, pass.runs, pass.run_label);
+        let result = pass.fetch().await;
+        progress.inc(1);
+        let run = match result {
+            Ok(lighthouse_run) => Some(lighthouse_run),
+            Err(e) => {
+                error!("❌ Run {} of {} failed: {}", i + 1, pass.run_label, e);
+                None
+            }
+        };
+        let slot = if i + 1 == pass.runs { slot.take() } else { None };
+        let _ = messages.send(RunMessage { label: pass.report_label.clone(), run, slot });
+    }
+}
+
+/// Starts every cache pass of every scenario against `url` in scenario order, at most
+/// `run.concurrency` at a time, and returns a receiver for each pass's collected runs in
+/// the same order.
+///
+/// Each pass runs in its own task and streams its runs to a [`collect_runs`] task. After
+/// [`request_shutdown`], no further scenario is started.
+fn dispatch_passes(run: &RunConfig, url: &str, progress: &ProgressBar) -> Vec<oneshot::Receiver<CollectedRuns>> {
+    let (messages, inbox) = mpsc::unbounded_channel();
+    let mut expected = HashMap::new();
+    let mut receivers = Vec::new();
+    let mut passes = Vec::new();
+    for scenario in &run.scenarios {
+        for (i, &cache) in run.cache_passes.iter().enumerate() {
+            let pass = CachePass::new(run, scenario, cache, url);
+            let (done, receiver) = oneshot::channel();
+            expected.insert(pass.report_label.clone(), (pass.runs as usize, done));
+            receivers.push(receiver);
+            passes.push((i == 0, pass));
+        }
+    }
+    tokio::spawn(collect_runs(inbox, expected, run.aggregation));
+
+    let limit = Arc::new(Semaphore::new(run.concurrency.max(1) as usize));
+    let progress = progress.clone();
+    tokio::spawn(async move {
+        for (starts_scenario, pass) in passes {
+            let Ok(slot) = Arc::clone(&limit).acquire_owned().await else {
+                break;
+            };
+            // A scenario's passes belong together, so only stop between scenarios.
+            if starts_scenario && shutdown_requested() {
+                info!("🛑 Stopping before scenario '{}' on {}", pass.label, pass.url);
+                break;
+            }
+            tokio::spawn(run_pass(pass, slot, progress.clone(), messages.clone()));
+        }
+    });
+    receivers
+}
+
+/// Runs every scenario against every URL, aggregating each cache pass's runs and
+/// comparing them with the history in `summary.json`.
+///
+/// Nothing is appended to `summary.json` or written to the summary reports; pass the
+/// result to the post-processors for that. With `options.dry_run` the Lighthouse
+/// commands are printed and an empty report is returned. After [`request_shutdown`],
+/// the scenarios that already finished are returned and the rest are skipped.
+pub async fn run_scenarios(run: &RunConfig) -> Result<RunReport, Box<dyn Error>> {
+    run_scenarios_with(run, |_| {}).await
+}
+
+/// Like [`run_scenarios`], calling `on_result` with each scenario's result as soon as
+/// it completes, e.g. to stream results out before the whole run finishes.
+///
+/// Cache passes run concurrently up to `run.concurrency`, but results are reported in
+/// scenario order, each as soon as it and the ones before it are done.
+pub async fn run_scenarios_with(
+    run: &RunConfig,
+    mut on_result: impl FnMut(&ScenarioResult),
+) -> Result<RunReport, Box<dyn Error>> {
+    run.validate()?;
+    let config = &run.config;
+    let out_dir = &config.output.out_dir;
+    let env = run.env_label.as_deref();
+    let progress = output::start_progress(run.total_runs(), run.show_progress && !run.options.dry_run);
+    let mut report = RunReport::default();
+
+    'urls: for url in &run.urls {
+        let url = url.as_str();
+        if shutdown_requested() {
+            info!("🛑 Stopping before {}", url);
+            break;
+        }
+        // The first selected scenario is the reference for each cache pass: the other scenarios'
+        // request origins are diffed against its run under the same cache state.
+        let mut reference_origins: HashMap<CacheState, (String, BTreeSet<String>)> = HashMap::new();
+
+        if run.options.dry_run {
+            for scenario in &run.scenarios {
+                for &cache in &run.cache_passes {
+                    CachePass::new(run, scenario, cache, url).fetch().await?;
+                }
+            }
+            continue;
+        }
+
+        let mut passes = dispatch_passes(run, url, &progress).into_iter();
+
+        for (index, scenario) in run.scenarios.iter().enumerate() {
+            let label = scenario.label.as_str();
+            let blocked = scenario.blocked.as_slice();
+            let mut pass_results = Vec::new();
+
+            for &cache in &run.cache_passes {
+                let CachePass { run_label, report_label, .. } = CachePass::new(run, scenario, cache, url);
+                // Passes the dispatcher never started, after a shutdown request, have no result.
+                let Some(done) = passes.next() else {
+                    break 'urls;
+                };
+                // `_slot` lives until the pass has been reported; see `CollectedRuns::slot`.
+                let Ok(CollectedRuns { attempted, runs, last, aggregate, slot: _slot }) = done.await else {
+                    // Passes skipped after a shutdown request never report; anything else is a bug.
+                    if shutdown_requested() {
+                        break 'urls;
+                    }
+                    return Err(format!("cache pass {} on {} finished without a result", report_label, url).into());
+                };
+
+                report.runs.record(attempted, runs.len());
+
+                let Some(last) = last else {
+                    error!("❌ All runs failed for scenario: {}", run_label);
+                    continue;
+                };
+
+                let metrics_in_seconds = aggregate.unwrap_or_default().to_seconds();
+                let fetch_time = Utc::now().to_rfc3339();
+
+                if last.was_redirected(url) {
+                    warn!("⚠️ {} redirected to {}; metrics reflect the final URL.", url, last.final_url);
+                }
+                if last.redirect_ms > 0.0 {
+                    warn!("⚠️ Redirects added {:.0} ms before the page started loading.", last.redirect_ms);
+                }
+
+                if let Some(required) = &run.require_lh_version {
+                    if &last.metadata.lighthouse_version != required {
+                        warn!(
+                            "⚠️ Expected Lighthouse {} but the report was produced by {}; results may not be comparable.",
+                            required, last.metadata.lighthouse_version
+                        );
+                    }
+                }
+
+                let previous = match &run.pinned_baseline {
+                    Some(pinned) => pinned.compare(&run_label, url, env, &metrics_in_seconds),
+                    None => compare_with_previous(out_dir, &run_label, url, env, &metrics_in_seconds)?,
+                };
+                let regression = detect_regression(
+                    out_dir,
+                    &run_label,
+                    url,
+                    env,
+                    metrics_in_seconds.performance_score,
+                    run.trend_window,
+                    run.trend_sigmas,
+                )?;
+
+                let entry = SummaryEntry {
+                    schema_version: SUMMARY_SCHEMA_VERSION,
+                    scenario: run_label.clone(),
+                    url: normalize_url(url),
+                    final_url: last.final_url.clone(),
+                    fetch_time,
+                    cache_state: cache.as_str().to_string(),
+                    env: env.map(str::to_string),
+                    blocked_patterns: merge_blocked_patterns(blocked, &run.denylist)
+                        .into_iter()
+                        .map(str::to_string)
+                        .collect(),
+                    metadata: last.metadata.clone(),
+                    metrics: metrics_in_seconds.clone(),
+                    dom: last.dom,
+                };
+
+                info!("Summary for scenario '{}':", run_label);
+                info!("{}", metrics_in_seconds.evaluate());
+                info!("Composite Score: {:.1}", metrics_in_seconds.composite_score(&config.scoring));
+                info!("Core Web Vitals:");
+                for (metric, rating) in metrics_in_seconds.classify_with(&config.cwv) {
+                    let rating = match rating {
+                        Rating::Good => rating.to_string().green(),
+                        Rating::NeedsImprovement => rating.to_string().yellow(),
+                        Rating::Poor => rating.to_string().red(),
+                    };
+                    info!("- {}: {}", metric, rating);
+                }
+
+                let budget = run.budgets.as_ref().map(|budgets| budgets.for_scenario(label)).unwrap_or_default();
+                let mut violations = budget.check(&metrics_in_seconds);
+                let byte_budgets = budget.byte_usage(&last.byte_breakdown);
+                if !byte_budgets.is_empty() {
+                    info!("Byte budgets:");
+                    for usage in &byte_budgets {
+                        info!(
+                            "- {:<12} {:>9.1} KiB of {:>9.1} KiB ({:>3.0}%){}",
+                            usage.resource_type,
+                            usage.bytes / 1024.0,
+                            usage.limit / 1024.0,
+                            usage.utilization(),
+                            if usage.is_over() { " 🚨" } else { "" }
+                        );
+                    }
+                    violations.extend(byte_budgets.iter().filter_map(|usage| usage.violation()));
+                }
+                for violation in &violations {
+                    warn!("🚨 Budget exceeded – {}", violation);
+                }
+
+                match (&previous, &run.pinned_baseline) {
+                    (Some(deltas), Some(_)) => {
+                        info!("Change since pinned baseline:");
+                        print_deltas(deltas);
+                    }
+                    (Some(deltas), None) => {
+                        info!("Change since previous run:");
+                        print_deltas(deltas);
+                    }
+                    (None, Some(_)) => warn!("⚠️ The baseline file has no entry for '{}' at {}.", run_label, url),
+                    (None, None) => info!("No previous run recorded for '{}' at {}.", run_label, url),
+                }
+
+                if let Some(r) = &regression {
+                    warn!(
+                        "⚠️ Performance score {:.1} is {:.1}σ below the mean of the last {} runs ({:.1} ± {:.1}).",
+                        r.current, r.sigmas(), r.window, r.mean, r.std_dev
+                    );
+                }
+
+                if config.baseline.auto_update {
+                    match update_moving_baseline(out_dir, &run_label, url, env, metrics_in_seconds.performance_score, &config.baseline)? {
+                        BaselineOutcome::Initialized(score) => info!("Moving baseline initialized at {:.1}.", score),
+                        BaselineOutcome::Steady(score) => info!("Within noise of moving baseline ({:.1}).", score),
+                        BaselineOutcome::Improving { baseline, streak } => info!(
+                            "Improved on moving baseline {:.1} ({}/{} confirming runs).",
+                            baseline, streak, config.baseline.confirmations
+                        ),
+                        BaselineOutcome::Shifted(shift) => info!(
+                            "📉 Moving baseline for '{}' shifted {:.1} -> {:.1} after confirmed improvement.",
+                            label, shift.from, shift.to
+                        ),
+                        BaselineOutcome::Regressed { baseline, score } => warn!(
+                            "⚠️ Score {:.1} is {:.1} points below the moving baseline {:.1}.",
+                            score, baseline - score, baseline
+                        ),
+                    }
+                }
+
+                if index == 0 {
+                    debug!("Request origins: {}", last.origins.len());
+                    reference_origins.insert(cache, (run_label.clone(), last.origins.clone()));
+                    if !last.third_parties.is_empty() {
+                        info!("Top third parties by main-thread time:");
+                        for third_party in last.third_parties.iter().take(5) {
+                            info!(
+                                "- {}: {:.0} ms main thread, {:.1} KiB",
+                                third_party.entity,
+                                third_party.main_thread_ms,
+                                third_party.transfer_bytes / 1024.0
+                            );
+                        }
+                    }
+                } else if let Some((reference, origins)) = reference_origins.get(&cache) {
+                    let diff = diff_origins(origins, &last.origins);
+                    info!("Request origins vs {}: {} removed, {} added", reference, diff.removed.len(), diff.added.len());
+                    for origin in &diff.removed {
+                        info!("  - {}", origin);
+                    }
+                    for origin in &diff.added {
+                        info!("  + {}", origin);
+                    }
+                }
+
+                info!("Top 5 Performance Bottlenecks:");
+                for (metric, value) in metrics_in_seconds.top_offenders() {
+                    info!("- {}: {:.2}", metric, value);
+                }
+
+                if !last.opportunities.is_empty() {
+                    info!("Top Opportunities:");
+                    for opportunity in last.opportunities.iter().take(5) {
+                        info!(
+                            "- {}: {:.0} ms, {:.1} KiB",
+                            opportunity.title,
+                            opportunity.savings_ms,
+                            opportunity.savings_bytes / 1024.0
+                        );
+                    }
+                }
+
+                if last.dom.total_elements > 0 {
+                    info!(
+                        "DOM: {} elements, {} levels deep, at most {} children per element",
+                        last.dom.total_elements, last.dom.max_depth, last.dom.max_children
+                    );
+                }
+
+                if !last.unused_javascript.is_empty() {
+                    info!("Top Unused JavaScript:");
+                    for (url, wasted, total) in last.unused_javascript.iter().take(5) {
+                        info!("- {}: {:.1} of {:.1} KiB unused", url, wasted / 1024.0, total / 1024.0);
+                    }
+                }
+
+                let chains = &last.critical_chains;
+                if !chains.is_empty() {
+                    info!(
+                        "Critical request chains: depth {}, longest path {:.0} ms:",
+                        chains.max_depth, chains.longest_duration_ms
+                    );
+                    for url in &chains.longest_path {
+                        info!("- {}", url);
+                    }
+                }
+
+                if !last.byte_breakdown.is_empty() {
+                    debug!("Transfer size by resource type:");
+                    for (resource_type, bytes) in &last.byte_breakdown {
+                        debug!("- {:<12} {:>9.1} KiB", resource_type, bytes / 1024.0);
+                    }
+                }
+
+                let trace = match (&last.trace_path, run.sample_trace) {
+                    (Some(path), true) => match parse_trace_json(&path.to_string_lossy(), run.options.max_report_bytes) {
+                        Ok(trace) => {
+                            info!("Trace for '{}' ({}):", run_label, path.display());
+                            print_trace_summary(&trace);
+                            Some(trace)
+                        }
+                        Err(e) => {
+                            warn!("⚠️ Could not parse trace {}: {}", path.display(), e);
+                            None
+                        }
+                    },
+                    (None, true) => {
+                        warn!("⚠️ No trace was saved for '{}'.", run_label);
+                        None
+                    }
+                    _ => None,
+                };
+
+                if !last.filmstrip.is_empty() {
+                    let timings: Vec<String> = last.filmstrip.iter().map(|f| format!("{:.0}", f.timing_ms)).collect();
+                    debug!("Filmstrip frames (ms): {}", timings.join(", "));
+                    report.filmstrips.push((report_label.clone(), last.filmstrip.clone()));
+                }
+
+                let result = ScenarioResult {
+                    entry,
+                    report_label,
+                    successful_runs: runs.len(),
+                    runs: runs.iter().map(LighthouseMetrics::to_seconds).collect(),
+                    budget_violations: violations,
+                    byte_budgets,
+                    opportunities: last.opportunities.clone(),
+                    critical_chains: last.critical_chains.clone(),
+                    trace,
+                    previous,
+                };
+                on_result(&result);
+                report.scenarios.push(result);
+
+                info!("✅ Completed scenario: {}", run_label);
+                pass_results.push((cache, metrics_in_seconds));
+            }
+
+            if let [(CacheState::Cold, cold), (CacheState::Warm, warm)] = pass_results.as_slice() {
+                info!("Cold vs warm cache for '{}':", label);
+                info!(
+                    "- Perf: {:.1} -> {:.1} ({:+.1})",
+                    cold.performance_score, warm.performance_score, warm.performance_score - cold.performance_score
+                );
+                info!(
+                    "- LCP: {:.2}s -> {:.2}s ({:+.2}s)",
+                    cold.largest_contentful_paint, warm.largest_contentful_paint,
+                    warm.largest_contentful_paint - cold.largest_contentful_paint
+                );
+                info!(
+                    "- TBT: {:.2}s -> {:.2}s ({:+.2}s)",
+                    cold.total_blocking_time, warm.total_blocking_time,
+                    warm.total_blocking_time - cold.total_blocking_time
+                );
+            }
+        }
+    }
+
+    output::finish_progress();
+    Ok(report)
+}
+
+/// Runs the command-line tool for already-parsed `args`: the report-only modes
+/// (`--reparse-dir`, `--since`, `--diff`, `--compare-summaries`), a user flow, or the
+/// scenarios, repeated or watched, with their post-processors and uploads.
+///
+/// Invalid input and failed gates (budgets, `--fail-on-regression`,
+/// `--min-success-rate`) are logged and turn into [`ExitCode::FAILURE`]; an `Err` is an
+/// unexpected failure such as an unwritable output directory. Logging must already be
+/// set up.
+pub async fn run_cli(args: Args) -> Result<ExitCode, Box<dyn Error>> {
+    info!("🚀 Performance Tracker starting...");
+    let mut config = Config::load(args.config.as_deref())?;
+    if let Some(out_dir) = &args.out_dir {
+        config.output.out_dir = out_dir.clone();
+    }
+    if let Some(format) = &args.timestamp_format {
+        config.output.timestamp_format = format.clone();
+    }
+    if let Err(e) = validate_timestamp_format(&config.output.timestamp_format) {
+        error!("❌ {}", e);
+        return Ok(ExitCode::FAILURE);
+    }
+    if (args.watch || args.repeat > 1) && !timestamp_has_minutes(&config.output.timestamp_format) {
+        let flag = if args.watch { "--watch" } else { "--repeat" };
+        error!("❌ {} needs a timestamp format with the time of day, so iterations don't overwrite each other", flag);
+        return Ok(ExitCode::FAILURE);
+    }
+    let audit_map = match args.audit_map.as_deref().map(AuditMap::load).transpose() {
+        Ok(audit_map) => audit_map.unwrap_or_default(),
+        Err(e) => {
+            error!("❌ {}", e);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    if let Some(dir) = &args.report_cache {
+        ReportCache::global().set_disk_dir(dir.clone());
+    }
+    if let Some(dir) = &args.reparse_dir {
+        let output = OutputConfig { out_dir: dir.clone(), ..config.output.clone() };
+        let entries = reparse_reports(dir, config.limits.max_report_bytes, &audit_map)?;
+        replace_summary(&entries, &output)?;
+        if output.write_txt {
+            // Only the days with re-parsed reports are rebuilt. Their old logs may hold runs
+            // whose reports weren't saved, so they are kept as `metrics_log_<date>.txt.bak`.
+            let logs: BTreeSet<PathBuf> = entries.iter().map(|entry| txt_log_path(dir, &entry.fetch_time)).collect();
+            for log in logs.iter().filter(|log| log.exists()) {
+                std::fs::rename(log, log.with_extension("txt.bak"))?;
+            }
+            for entry in &entries {
+                save_metrics_to_txt(&entry.scenario, &entry.metrics, &entry.url, &entry.fetch_time, &entry.metadata, &output)?;
+            }
+        }
+        info!("Re-parsed {} reports in {}", entries.len(), dir.display());
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(since) = args.since {
+        let out_dir = &config.output.out_dir;
+        summarize_reports_since(out_dir, since, args.env_label.as_deref(), config.limits.max_report_bytes, &audit_map)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some([before, after]) = args.diff.as_deref() {
+        let max_bytes = config.limits.max_report_bytes;
+        let mut loaded = Vec::new();
+        for path in [before, after] {
+            match load_report_metrics(path, max_bytes, &audit_map) {
+                Ok(metrics) => loaded.push(metrics),
+                Err(e) => {
+                    error!("❌ Could not load {}: {}", path.display(), e);
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+        }
+        status!("Diff: {} -> {}", before.display(), after.display());
+        print_diff_table(&diff_metrics(&loaded[0], &loaded[1]));
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some([old, new]) = args.compare_summaries.as_deref() {
+        let comparison = match compare_summaries(old, new) {
+            Ok(comparison) => comparison,
+            Err(e) => {
+                error!("❌ Could not compare summaries: {}", e);
+                return Ok(ExitCode::FAILURE);
+            }
+        };
+        status!("Summary comparison: {} -> {}", old.display(), new.display());
+        print_summary_comparison(&comparison);
+        return Ok(if comparison.verdict() == Verdict::Regression { ExitCode::FAILURE } else { ExitCode::SUCCESS });
+    }
+    if args.cpu_slowdown.is_some() {
+        config.throttling.cpu_slowdown_multiplier = args.cpu_slowdown;
+    }
+    if args.rtt_ms.is_some() {
+        config.throttling.rtt_ms = args.rtt_ms;
+    }
+    if args.throughput_kbps.is_some() {
+        config.throttling.throughput_kbps = args.throughput_kbps;
+    }
+    // A dry run only prints the Lighthouse commands, so it must not create the output directory or the database.
+    if !args.dry_run {
+        std::fs::create_dir_all(&config.output.out_dir)?;
+    }
+    let out_dir = config.output.out_dir.clone();
+    let budgets = args.budget.as_deref().map(Budgets::load).transpose()?;
+    let db = args.db.as_deref().filter(|_| !args.dry_run).map(MetricsDb::open).transpose()?;
+    let denylist = args.denylist.as_deref().map(load_denylist).transpose()?.unwrap_or_default();
+    if let Some(Err(e)) = args.extra_headers.as_deref().map(validate_extra_headers) {
+        error!("❌ {}", e);
+        return Ok(ExitCode::FAILURE);
+    }
+    if let Some(Err(e)) = args.proxy.as_deref().map(validate_proxy) {
+        error!("❌ {}", e);
+        return Ok(ExitCode::FAILURE);
+    }
+    #[cfg(not(feature = "s3"))]
+    if args.s3.is_some() {
+        error!("❌ --s3 needs a build with `--features s3`");
+        return Ok(ExitCode::FAILURE);
+    }
+    if let Some(path) = args.lh_config.as_deref().filter(|path| !path.is_file()) {
+        error!("❌ Lighthouse config {} not found", path.display());
+        return Ok(ExitCode::FAILURE);
+    }
+    let max_report_bytes = config.limits.max_report_bytes;
+    let pinned_baseline = match args
+        .baseline_file
+        .as_deref()
+        .map(|path| PinnedBaseline::load(path, max_report_bytes, &audit_map))
+        .transpose()
+    {
+        Ok(pinned) => pinned,
+        Err(e) => {
+            error!("❌ Could not load baseline file: {}", e);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let urls = match args.urls_file.as_deref().map(load_url_list).transpose() {
+        Ok(urls) => urls.unwrap_or_else(|| vec![DEFAULT_URL.to_string()]),
+        Err(e) => {
+            error!("❌ {}", e);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    if let Some(script) = &args.flow_script {
+        if !script.is_file() {
+            error!("❌ Flow script {} not found", script.display());
+            return Ok(ExitCode::FAILURE);
+        }
+        let options = LighthouseOptions {
+            max_report_bytes: config.limits.max_report_bytes,
+            dry_run: args.dry_run,
+            run_timeout: Duration::from_secs(args.run_timeout),
+            out_dir: out_dir.clone(),
+            proxy: args.proxy.clone(),
+            audit_map,
+            timestamp_format: config.output.timestamp_format.clone(),
+            ..Default::default()
+        };
+        let mut results = Vec::new();
+        for url in &urls {
+            info!("🚶 Running user flow {} against {}", script.display(), url);
+            let steps = match run_flow_script(script, url, &options).await {
+                Ok(steps) => steps,
+                Err(e) => {
+                    error!("❌ User flow failed for {}: {}", url, e);
+                    return Ok(ExitCode::FAILURE);
+                }
+            };
+            for (step, metrics) in &steps {
+                let result = serde_json::json!({ "url": url, "step": step, "metrics": metrics });
+                match args.format {
+                    OutputFormat::Ndjson => println!("{}", serde_json::to_string(&result)?),
+                    OutputFormat::Json => results.push(result),
+                    _ => {}
+                }
+            }
+            print_flow_steps(script, url, &steps);
+        }
+        if args.format == OutputFormat::Json {
+            println!("{}", serde_json::to_string_pretty(&results)?);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let configured = if config.scenarios.is_empty() {
+        builtin_scenarios()
+    } else {
+        config.scenarios.clone()
+    };
+    let scenarios = match select_scenarios(configured, &args.only, &args.skip) {
+        Ok(selected) => selected,
+        Err(e) => {
+            error!("❌ {}", e);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    let mut all_patterns: Vec<&str> = Vec::new();
+    for scenario in &scenarios {
+        for pattern in merge_blocked_patterns(&scenario.blocked, &denylist) {
+            if !all_patterns.contains(&pattern) {
+                all_patterns.push(pattern);
+            }
+        }
+    }
+    if let Err(e) = validate_blocked_patterns(&all_patterns) {
+        if args.strict_patterns {
+            error!("❌ {}", e);
+            return Ok(ExitCode::FAILURE);
+        }
+        warn!("⚠️ {}; these patterns will not block anything.", e);
+    }
+
+    if !args.dry_run {
+        match preflight(&args.lighthouse_path) {
+            Ok(version) => info!("Using Lighthouse {}", version),
+            Err(e) => {
+                error!("❌ {}", e);
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        if let Some(ready_url) = &args.wait_for {
+            info!("⏳ Waiting for {} to respond...", ready_url);
+            if let Err(e) = wait_for_url(ready_url, Duration::from_secs(args.wait_timeout)).await {
+                error!("❌ {}", e);
+                return Ok(ExitCode::FAILURE);
+            }
+            info!("✅ {} is up", ready_url);
+        }
+    }
+
+    let env = args.env_label.as_deref();
+    let run = RunConfig {
+        options: LighthouseOptions {
+            max_report_bytes: config.limits.max_report_bytes,
+            dry_run: args.dry_run,
+            throttling: config.throttling.clone(),
+            extra_headers: args.extra_headers.clone(),
+            chrome_flags: args.chrome_flags.clone(),
+            proxy: args.proxy.clone(),
+            lighthouse_path: args.lighthouse_path.clone(),
+            save_filmstrip: args.save_filmstrip,
+            run_timeout: Duration::from_secs(args.run_timeout),
+            compress: args.compress,
+            save_report: !args.no_save_reports,
+            lh_config: args.lh_config.clone(),
+            audit_map,
+            timestamp_format: config.output.timestamp_format.clone(),
+            ..Default::default()
+        },
+        // With a URL list, report files also carry the URL so runs of the same scenario don't collide.
+        per_url_reports: args.urls_file.is_some(),
+        urls,
+        scenarios,
+        denylist,
+        budgets,
+        cache_passes: args.cache_passes(),
+        runs: args.runs,
+        warm_runs: args.runs_for(CacheState::Warm),
+        aggregation: args.aggregation,
+        retry: RetryPolicy {
+            max_retries: args.max_retries,
+            base_delay: Duration::from_millis(args.retry_base_delay_ms),
+        },
+        env_label: args.env_label.clone(),
+        require_lh_version: args.require_lh_version.clone(),
+        sample_trace: args.sample_trace,
+        trend_window: args.trend_window,
+        trend_sigmas: args.trend_sigmas,
+        show_progress: std::io::stdout().is_terminal() && !args.quiet && args.format == OutputFormat::Text,
+        pinned_baseline,
+        concurrency: args.concurrency,
+        config: config.clone(),
+    };
+    let csv_path = args
+        .export_csv
+        .clone()
+        .or_else(|| config.output.write_csv.then(|| out_dir.join("summary.csv")));
+    let processors = builtin_processors(&config.output, csv_path, db);
+    let mut report = RunReport::default();
+    let mut variance = VarianceReport::default();
+    let mut result = None;
+
+    if args.watch {
+        tokio::spawn(async {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                info!("🛑 Ctrl-C: finishing the scenario in flight, then stopping. Press Ctrl-C again to quit now.");
+                request_shutdown();
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    std::process::exit(130);
+                }
+            }
+        });
+    }
+
+    let iterations = if args.watch { u32::MAX } else { args.repeat };
+    for iteration in 1..=iterations {
+        if shutdown_requested() {
+            break;
+        }
+        if iteration > 1 && args.cooldown > 0 {
+            info!("⏸️ Cooling down for {}s before the next iteration...", args.cooldown);
+            tokio::time::sleep(Duration::from_secs(args.cooldown)).await;
+        }
+        let started = tokio::time::Instant::now();
+        // Each iteration names its files after its own start time, so it doesn't overwrite the last.
+        let previous = config.output.timestamp();
+        start_new_run();
+        while iteration > 1 && config.output.timestamp() == previous {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            start_new_run();
+        }
+        if args.watch {
+            info!("=== Watch iteration {} ({}) ===", iteration, Local::now().format("%Y-%m-%d %H:%M:%S"));
+        } else if args.repeat > 1 {
+            info!("=== Iteration {}/{} ===", iteration, args.repeat);
+        }
+
+        let iteration_report = run_scenarios_with(&run, |scenario| {
+            if args.format == OutputFormat::Ndjson {
+                match serde_json::to_string(scenario) {
+                    Ok(line) => println!("{}", line),
+                    Err(e) => warn!("⚠️ Could not serialize result for {}: {}", scenario.report_label, e),
+                }
+            }
+        })
+        .await?;
+        if args.dry_run {
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let iteration_result = RunResult {
+            url: match run.urls.as_slice() {
+                [url] => url.clone(),
+                urls => format!("{} URLs", urls.len()),
+            },
+            fetch_time: Utc::now().to_rfc3339(),
+            scenarios: iteration_report.scenarios.iter().map(|s| s.entry.clone()).collect(),
+            filmstrips: iteration_report.filmstrips.clone(),
+            critical_chains: iteration_report
+                .scenarios
+                .iter()
+                .filter(|s| !s.critical_chains.is_empty())
+                .map(|s| (s.report_label.clone(), s.critical_chains.clone()))
+                .collect(),
+        };
+        run_processors(&processors, &iteration_result)?;
+        result = Some(iteration_result);
+        if !args.watch {
+            variance.add(&iteration_report);
+            report.extend(iteration_report);
+            continue;
+        }
+
+        // Watching runs indefinitely, so only the latest iteration is kept for the final report.
+        report = iteration_report;
+        let next = started + Duration::from_secs(args.interval * 60);
+        if !shutdown_requested() {
+            let wait = next.saturating_duration_since(tokio::time::Instant::now());
+            let next_at = Local::now() + chrono::Duration::from_std(wait).unwrap_or_default();
+            info!("⏰ Next iteration at {}; press Ctrl-C to stop.", next_at.format("%H:%M:%S"));
+        }
+        while !shutdown_requested() && tokio::time::Instant::now() < next {
+            let remaining = next.saturating_duration_since(tokio::time::Instant::now());
+            tokio::time::sleep(remaining.min(Duration::from_secs(1))).await;
+        }
+    }
+    let Some(result) = result else {
+        return Ok(ExitCode::SUCCESS);
+    };
+
+    info!("✅ All Lighthouse scenarios completed.");
+
+    if args.no_save_reports {
+        info!("Skipping the summary table: it reads the saved reports, and --no-save-reports kept none.");
+    } else {
+        summarize_local_json_reports(&out_dir, env, config.limits.max_report_bytes, &run.options.audit_map)?;
+    }
+    if args.repeat > 1 {
+        variance.print();
+    }
+    if let Some([base, candidate]) = args.compare_p75.as_deref() {
+        print_p75_comparison(&report, base, candidate);
+    }
+
+    if let Some(webhook) = &args.webhook {
+        if let Err(e) = post_summary(webhook, args.webhook_format, &result).await {
+            warn!("⚠️ Could not post summary to webhook: {}", e);
+        }
+    }
+
+    if let Some(days) = args.compare_period {
+        info!("=== {}-Day Comparison ===", days);
+        for entry in &result.scenarios {
+            for metric in ["performance_score", "largest_contentful_paint"] {
+                match period_comparison(&out_dir, &entry.scenario, env, metric, chrono::Duration::days(days))? {
+                    Some(d) => info!(
+                        "{:<18} {}: {:.2} ({}) -> {:.2} ({}), {:+.2}{}",
+                        entry.scenario,
+                        metric,
+                        d.from_value,
+                        d.from_time.format("%Y-%m-%d"),
+                        d.to_value,
+                        d.to_time.format("%Y-%m-%d"),
+                        d.absolute,
+                        d.percent.map(|p| format!(" ({:+.1}%)", p)).unwrap_or_default()
+                    ),
+                    None => info!("{:<18} {}: no run near {} days ago", entry.scenario, metric, days),
+                }
+            }
+        }
+    }
+
+    if let Some(path) = &args.junit {
+        write_junit(&report, path)?;
+    }
+
+    // Local files stay where they are; a failed upload only warns.
+    #[cfg(feature = "s3")]
+    if let Some(target) = &args.s3 {
+        let uploaded = match run_artifacts(&out_dir, &config.output.timestamp()) {
+            Ok(files) => upload_artifacts(target, &files).await.map(|n| (n, files.len())),
+            Err(e) => Err(e.into()),
+        };
+        match uploaded {
+            Ok((n, total)) => info!("☁️ Uploaded {}/{} artifacts to s3://{}/{}", n, total, target.bucket, target.prefix),
+            Err(e) => warn!("⚠️ Could not upload artifacts to S3: {}", e),
+        }
+    }
+
+    if args.format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+
+    let budget_failures: Vec<&ScenarioResult> =
+        report.scenarios.iter().filter(|s| !s.budget_violations.is_empty()).collect();
+    let score_drops: Vec<(&str, &MetricDelta)> = match args.fail_on_regression {
+        Some(max_drop) => report
+            .scenarios
+            .iter()
+            .filter_map(|s| {
+                let deltas = s.previous.as_ref()?;
+                let d = deltas.iter().find(|d| d.metric == "performance_score")?;
+                (-d.delta() > max_drop).then_some((s.report_label.as_str(), d))
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    if !budget_failures.is_empty() {
+        error!("❌ Performance budget violated in {} scenario(s):", budget_failures.len());
+        for scenario in &budget_failures {
+            for violation in &scenario.budget_violations {
+                error!("- {}: {}", scenario.report_label, violation);
+            }
+        }
+    }
+
+    if !score_drops.is_empty() {
+        error!(
+            "❌ Performance score dropped by more than {} points since {} in {} scenario(s):",
+            args.fail_on_regression.unwrap_or_default(),
+            if args.baseline_file.is_some() { "the pinned baseline" } else { "the previous run" },
+            score_drops.len()
+        );
+        for (label, d) in &score_drops {
+            error!("- {}: {:.1} -> {:.1} ({:+.1})", label, d.previous, d.current, d.delta());
+        }
+    }
+
+    if report.runs.attempted > 0 {
+        info!("Completed: {}", report.runs);
+    }
+
+    let too_few_runs = args
+        .min_success_rate
+        .filter(|&min| report.runs.success_rate() < min);
+    if let Some(min) = too_few_runs {
+        error!(
+            "❌ Only {:.1}% of runs succeeded; --min-success-rate requires {}%.",
+            report.runs.success_rate(),
+            min
+        );
+    }
+
+    if !budget_failures.is_empty() || !score_drops.is_empty() || too_few_runs.is_some() {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_with_score(score: f64) -> Option<LighthouseRun> {
+        let metrics = LighthouseMetrics { performance_score: score, ..Default::default() };
+        Some(LighthouseRun { metrics, ..Default::default() })
+    }
+
+    #[tokio::test]
+    async fn collector_groups_interleaved_runs_by_label() {
+        let (messages, inbox) = mpsc::unbounded_channel();
+        let mut expected = HashMap::new();
+        let (baseline_done, baseline) = oneshot::channel();
+        let (no_tealium_done, no_tealium) = oneshot::channel();
+        let (never_done, never_started) = oneshot::channel();
+        expected.insert("baseline".to_string(), (3, baseline_done));
+        expected.insert("no-tealium".to_string(), (2, no_tealium_done));
+        expected.insert("no-appd".to_string(), (1, never_done));
+        let collector = tokio::spawn(collect_runs(inbox, expected, Aggregation::Median));
+
+        let send = |label: &str, run| messages.send(RunMessage { label: label.to_string(), run, slot: None }).unwrap();
+        send("baseline", run_with_score(0.8));
+        send("no-tealium", run_with_score(0.9));
+        send("baseline", None);
+        send("no-tealium", run_with_score(0.7));
+        send("baseline", run_with_score(0.6));
+        drop(messages);
+
+        let baseline = baseline.await.unwrap();
+        assert_eq!((baseline.attempted, baseline.runs.len()), (3, 2));
+        assert_eq!(baseline.last.unwrap().metrics.performance_score, 0.6);
+        let no_tealium = no_tealium.await.unwrap();
+        assert_eq!(no_tealium.aggregate.unwrap().performance_score, 0.8);
+        // A pass whose runs never arrived is reported as not run.
+        assert!(never_started.await.is_err());
+        collector.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn runs_that_could_not_be_reported_are_rejected_up_front() {
+        let mut no_runs = RunConfig::new(Config::default());
+        no_runs.runs = 0;
+        let mut duplicates = RunConfig::new(Config::default());
+        duplicates.scenarios.push(duplicates.scenarios[0].clone());
+
+        let no_runs = run_scenarios(&no_runs).await.unwrap_err().to_string();
+        let duplicates = run_scenarios(&duplicates).await.unwrap_err().to_string();
+
+        assert_eq!(no_runs, "the cold cache pass needs at least one run");
+        assert!(duplicates.starts_with("duplicate scenario label(s): "), "{}", duplicates);
+    }
+}
@@ -1,46 +1,254 @@
+use std::collections::{BTreeMap, HashSet};
 use std::error::Error;
-use std::fs::{self, read_to_string, OpenOptions};
+use std::fs::{self, read_to_string};
 use std::io::{self, Write};
-use std::path::Path;
-use chrono::Local;
-use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use colored::Colorize;
+use comfy_table::presets::UTF8_FULL_CONDENSED;
+use comfy_table::{Cell, CellAlignment, Color, Table};
+use log::{info, warn};
+use serde::Serialize;
+use serde_json::Value;
 
-use crate::metrics::LighthouseMetrics;
+use crate::config::OutputConfig;
+use crate::lighthouse::{
+    extract_dom_stats, extract_final_url, AuditMap, extract_run_metadata, normalize_url, same_url, slugify_url, DomStats,
+    RunMetadata,
+};
+use crate::lighthouse_summary::{rank_against_baseline, ScenarioMetrics};
+use crate::metrics::{LighthouseMetrics, MetricsError, Rating};
+use crate::output;
+use crate::report_cache::ReportCache;
+use crate::status;
 
-/// Safely updates or creates `summary.json` with a new performance entry.
-pub fn update_summary(
-    scenario: &str,
-    url: &str,
-    fetch_time: &str,
-    metrics: &LighthouseMetrics,
-) -> io::Result<()> {
-    let path = "summary.json";
+/// Location of `summary.json` inside `out_dir`.
+pub fn summary_path(out_dir: &Path) -> PathBuf {
+    out_dir.join("summary.json")
+}
 
-    let mut entries = if Path::new(path).exists() {
-        let content = read_to_string(path)?;
-        serde_json::from_str::<Vec<Value>>(&content).unwrap_or_default()
-    } else {
-        Vec::new()
+/// Version of the `summary.json` entry layout written by this build.
+///
+/// Bump it whenever [`SummaryEntry`] changes shape, and teach [`migrate_entry`] to
+/// upgrade entries written by the previous version.
+pub const SUMMARY_SCHEMA_VERSION: u64 = 2;
+
+/// Upgrades an entry written by an older version in place.
+///
+/// Entries without a `schema_version` predate versioning (version 0); they may lack
+/// metrics added since (such as INP) and the per-run fields added alongside them,
+/// which are filled with the values a new run of the same setup would record.
+/// Version 1 entries predate the DOM breakdown, which reads 0 like other absent audits.
+fn migrate_entry(entry: &mut Value) {
+    let Some(fields) = entry.as_object_mut() else {
+        return;
     };
+    let version = fields.get("schema_version").and_then(Value::as_u64).unwrap_or(0);
+    if version > SUMMARY_SCHEMA_VERSION {
+        warn!(
+            "⚠️ summary.json entry has schema version {}, newer than this build's {}; reading it as is.",
+            version, SUMMARY_SCHEMA_VERSION
+        );
+        return;
+    }
 
-    let new_entry = json!({
-        "scenario": scenario,
-        "url": url,
-        "fetch_time": fetch_time,
-        "metrics": metrics
-    });
+    if version < 1 {
+        if let Some(metrics) = fields.get_mut("metrics").and_then(Value::as_object_mut) {
+            for field in LighthouseMetrics::FIELD_NAMES {
+                metrics.entry(field).or_insert(Value::from(0.0));
+            }
+        }
+        let url = fields.get("url").cloned().unwrap_or_default();
+        fields.entry("final_url").or_insert(url);
+        fields.entry("cache_state").or_insert(Value::from("cold"));
+        fields.entry("blocked_patterns").or_insert(Value::Array(Vec::new()));
+    }
+    if version < 2 {
+        fields.entry("dom").or_insert_with(|| serde_json::json!(DomStats::default()));
+    }
+
+    fields.insert("schema_version".to_string(), Value::from(SUMMARY_SCHEMA_VERSION));
+}
+
+/// Reads all entries from a summary file, treating a missing or empty file as empty history.
+/// Entries written by older versions are upgraded with [`migrate_entry`].
+///
+/// A file that doesn't parse is moved aside to `<name>.corrupt-<timestamp>` rather than
+/// discarded, so its history can be recovered by hand, and an empty history is returned.
+pub fn load_summary_entries(path: &Path) -> io::Result<Vec<Value>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
 
-    entries.push(new_entry);
+    let content = read_to_string(path)?;
+    if content.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    match serde_json::from_str::<Vec<Value>>(&content) {
+        Ok(mut entries) => {
+            entries.iter_mut().for_each(migrate_entry);
+            Ok(entries)
+        }
+        Err(e) => {
+            let backup = move_aside_corrupt(path)?;
+            warn!(
+                "⚠️ {} could not be parsed ({}); moved it to {} and starting a new history.",
+                path.display(),
+                e,
+                backup.display()
+            );
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Renames an unparseable `path` to `<name>.corrupt-<timestamp>` so it is kept for
+/// inspection instead of being overwritten, and returns where it went.
+pub(crate) fn move_aside_corrupt(path: &Path) -> io::Result<PathBuf> {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".corrupt-{}", Utc::now().format("%Y%m%dT%H%M%S")));
+    fs::rename(path, &backup)?;
+    Ok(PathBuf::from(backup))
+}
+
+/// Replaces `path` with `contents` via a temporary file and a rename, so a crash
+/// mid-write leaves either the old file or the new one, never a truncated mix.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+/// Everything recorded about one aggregated scenario run in `summary.json`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryEntry {
+    /// Layout version of the entry; see [`SUMMARY_SCHEMA_VERSION`].
+    pub schema_version: u64,
+    pub scenario: String,
+    pub url: String,
+    pub final_url: String,
+    pub fetch_time: String,
+    pub cache_state: String,
+    /// The `--env-label` the run was made with, e.g. `staging`; history is only compared within one environment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<String>,
+    /// URL patterns blocked for this run, so the entry stays meaningful if the scenario changes later.
+    pub blocked_patterns: Vec<String>,
+    pub metadata: RunMetadata,
+    pub metrics: LighthouseMetrics,
+    pub dom: DomStats,
+}
+
+/// Safely updates or creates `summary.json` with a new performance entry.
+pub fn update_summary(entry: &SummaryEntry, output: &OutputConfig) -> io::Result<()> {
+    if !output.write_json {
+        return Ok(());
+    }
+
+    let path = summary_path(&output.out_dir);
+    let mut entries = load_summary_entries(&path)?;
+
+    entries.push(serde_json::to_value(entry)?);
 
     let pretty = serde_json::to_string_pretty(&entries)?;
-    let mut file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(path)?;
-    file.write_all(pretty.as_bytes())?;
+    write_atomically(&path, pretty.as_bytes())
+}
 
-    Ok(())
+/// Replaces `summary.json` with `entries`, keeping the previous file as `summary.json.bak`.
+pub fn replace_summary(entries: &[SummaryEntry], output: &OutputConfig) -> io::Result<()> {
+    if !output.write_json {
+        return Ok(());
+    }
+
+    let path = summary_path(&output.out_dir);
+    if path.exists() {
+        fs::copy(&path, path.with_extension("json.bak"))?;
+    }
+    let pretty = serde_json::to_string_pretty(entries)?;
+    write_atomically(&path, pretty.as_bytes())
+}
+
+/// Combines several `summary.json` files, e.g. one per CI shard, into `out`.
+///
+/// Entries are deduplicated by scenario, URL and fetch time, sorted oldest first and
+/// written atomically. Missing or empty inputs contribute nothing; `out` may also be
+/// one of the inputs. Returns the number of entries written.
+pub fn merge(paths: &[&str], out: &str) -> io::Result<usize> {
+    let mut merged: Vec<Value> = Vec::new();
+    let mut seen = HashSet::new();
+    for path in paths {
+        for entry in load_summary_entries(Path::new(path))? {
+            let key = ["scenario", "url", "fetch_time"].map(|field| entry[field].to_string());
+            if seen.insert(key) {
+                merged.push(entry);
+            }
+        }
+    }
+    merged.sort_by_key(|entry| {
+        let time = entry["fetch_time"].as_str().unwrap_or_default();
+        (DateTime::parse_from_rfc3339(time).ok(), time.to_string())
+    });
+
+    let pretty = serde_json::to_string_pretty(&merged)?;
+    write_atomically(Path::new(out), pretty.as_bytes())?;
+    Ok(merged.len())
+}
+
+/// Rebuilds a summary entry from a saved report, without running Lighthouse.
+///
+/// The URL, fetch time, cache state and blocked patterns come from the report itself;
+/// the metrics are re-extracted, so improvements to [`LighthouseMetrics::from_json_with`] apply.
+pub fn entry_from_report(
+    scenario: &str,
+    env: Option<&str>,
+    json: &Value,
+    audits: &AuditMap,
+) -> Result<SummaryEntry, Box<dyn Error>> {
+    let url = normalize_url(json["requestedUrl"].as_str().unwrap_or_default());
+    let settings = &json["configSettings"];
+    Ok(SummaryEntry {
+        schema_version: SUMMARY_SCHEMA_VERSION,
+        scenario: scenario.to_string(),
+        final_url: extract_final_url(json).unwrap_or_else(|| url.clone()),
+        url,
+        fetch_time: json["fetchTime"].as_str().unwrap_or_default().to_string(),
+        cache_state: if settings["disableStorageReset"].as_bool() == Some(true) { "warm" } else { "cold" }.to_string(),
+        env: env.map(str::to_string),
+        blocked_patterns: settings["blockedUrlPatterns"]
+            .as_array()
+            .map(|patterns| patterns.iter().filter_map(|p| p.as_str().map(str::to_string)).collect())
+            .unwrap_or_default(),
+        metadata: extract_run_metadata(json),
+        metrics: LighthouseMetrics::from_json_with(json, audits)?.to_seconds(),
+        dom: extract_dom_stats(json),
+    })
+}
+
+/// Re-extracts a summary entry from every saved `lighthouse_report_*.json(.gz)` in `dir`,
+/// oldest first. Reports that can't be read or parsed are skipped with a warning.
+pub fn reparse_reports(dir: &Path, max_report_bytes: u64, audits: &AuditMap) -> io::Result<Vec<SummaryEntry>> {
+    let mut entries = Vec::new();
+    for dir_entry in fs::read_dir(dir)? {
+        let path = dir_entry?.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let Some(report) = parse_report_filename(name) else {
+            continue;
+        };
+        let entry = ReportCache::global()
+            .load(&path, max_report_bytes)
+            .and_then(|json| entry_from_report(report.scenario_for(requested_url(&json)), report.env, &json, audits));
+        match entry {
+            Ok(entry) => entries.push(entry),
+            Err(e) => warn!("⚠️ Skipping {}: {}", name, e),
+        }
+    }
+    entries.sort_by(|a, b| a.fetch_time.cmp(&b.fetch_time));
+    Ok(entries)
 }
 
 /// Lists all local Lighthouse JSON reports.
@@ -48,53 +256,264 @@ pub async fn list_local_reports() -> io::Result<()> {
     for entry in fs::read_dir(".")? {
         let path = entry?.path();
         if path.is_file() && path.to_string_lossy().contains("lighthouse_report") {
-            println!("Found report: {}", path.display());
+            info!("Found report: {}", path.display());
         }
     }
     Ok(())
 }
 
-/// Prints a tabular summary of today's Lighthouse JSON reports.
-pub fn summarize_local_json_reports() -> Result<(), Box<dyn Error>> {
-    println!("\n=== Performance Summary Table ===");
+/// Joins a scenario label and `--env-label` in report file names.
+pub const ENV_SEPARATOR: char = '@';
 
-    let today = Local::now().format("%Y-%m-%d").to_string();
-    let pattern = "lighthouse_report_";
+/// The parts of a saved report's file name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportFileName<'a> {
+    pub scenario: &'a str,
+    /// The `--env-label` the report was made with, if any.
+    pub env: Option<&'a str>,
+    /// The whole timestamp segment, e.g. `2024-01-01T09-30-00`, or just the date for reports
+    /// saved before file names carried the time.
+    pub timestamp: &'a str,
+    pub date: NaiveDate,
+}
 
-    for entry in fs::read_dir(".")? {
+impl<'a> ReportFileName<'a> {
+    /// The scenario label without the URL slug that runs with `--urls-file` add to report
+    /// names (`<label>_<slug>`), given the URL the report audited. Slugs never contain `_`,
+    /// so a label is only shortened when it ends in exactly that URL's slug.
+    pub fn scenario_for(&self, url: &str) -> &'a str {
+        let slug = slugify_url(url);
+        self.scenario
+            .strip_suffix(slug.as_str())
+            .and_then(|label| label.strip_suffix('_'))
+            .filter(|label| !label.is_empty())
+            .unwrap_or(self.scenario)
+    }
+}
+
+/// The URL a Lighthouse report was asked to audit.
+fn requested_url(json: &Value) -> &str {
+    json["requestedUrl"].as_str().unwrap_or_default()
+}
+
+/// Splits a report file name such as
+/// `lighthouse_report_no-tealium@staging_2024-01-01T09-30-00.json(.gz)` into its scenario
+/// label, optional environment and timestamp. Returns `None` for anything else.
+///
+/// Timestamps always start with the date (see [`crate::config::validate_timestamp_format`]), so reports
+/// named with a custom `--timestamp-format`, or with only the date, are matched to their day too.
+pub fn parse_report_filename(name: &str) -> Option<ReportFileName<'_>> {
+    let stem = name.strip_suffix(".gz").unwrap_or(name);
+    let stem = stem.strip_prefix("lighthouse_report_")?.strip_suffix(".json")?;
+    // Labels may contain underscores (e.g. `<label>_<url slug>` with `--urls-file`), so the
+    // timestamp is the last segment; see [`ReportFileName::scenario_for`] for the slug.
+    let (label, timestamp) = stem.rsplit_once('_')?;
+    // Timestamps never contain a dot; this skips assets saved next to a report, such as
+    // `lighthouse_report_baseline_<timestamp>-0.trace.json`.
+    if timestamp.contains('.') {
+        return None;
+    }
+    let date = NaiveDate::parse_from_str(timestamp.get(..10)?, "%Y-%m-%d").ok()?;
+    let (scenario, env) = match label.split_once(ENV_SEPARATOR) {
+        Some((scenario, env)) => (scenario, Some(env)),
+        None => (label, None),
+    };
+    (!scenario.is_empty()).then_some(ReportFileName { scenario, env, timestamp, date })
+}
+
+/// Whether a `summary.json` entry was recorded in environment `env` (`None` for unlabelled runs).
+pub fn same_env(entry: &Value, env: Option<&str>) -> bool {
+    entry["env"].as_str() == env
+}
+
+/// Reads the summary-table metrics (score, FCP, LCP, TTI, TBT in seconds, CLS) from a report,
+/// with audit IDs remapped by `audits`.
+fn scenario_row(name: &str, json: &Value, audits: &AuditMap) -> Result<ScenarioMetrics, MetricsError> {
+    Ok(ScenarioMetrics::new(name, &LighthouseMetrics::from_json_with(json, audits)?.to_seconds()))
+}
+
+/// One saved report read by [`load_report_rows`].
+struct ReportRow {
+    scenario: String,
+    timestamp: String,
+    date: NaiveDate,
+    row: ScenarioMetrics,
+}
+
+/// Every parseable report in `out_dir` from environment `env` dated on or after `since`.
+///
+/// Reports that are too large or not valid JSON are skipped with a warning.
+fn load_report_rows(
+    out_dir: &Path,
+    since: NaiveDate,
+    env: Option<&str>,
+    max_report_bytes: u64,
+    audits: &AuditMap,
+) -> io::Result<Vec<ReportRow>> {
+    let mut rows = Vec::new();
+    for entry in fs::read_dir(out_dir)? {
         let path = entry?.path();
-        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-            if name.starts_with(pattern) && name.ends_with(&format!("{}.json", today)) {
-                let raw = fs::read_to_string(&path)?;
-                let json: Value = serde_json::from_str(&raw)?;
-
-                let scenario = name
-                    .strip_prefix(pattern)
-                    .unwrap_or("unknown")
-                    .strip_suffix(&format!("_{}.json", today))
-                    .unwrap_or("unknown");
-
-                let perf = json["categories"]["performance"]["score"]
-                    .as_f64()
-                    .unwrap_or(0.0) * 100.0;
-                let fcp = json["audits"]["first-contentful-paint"]["numericValue"]
-                    .as_f64()
-                    .unwrap_or(0.0) / 1000.0;
-                let lcp = json["audits"]["largest-contentful-paint"]["numericValue"]
-                    .as_f64()
-                    .unwrap_or(0.0) / 1000.0;
-                let tti = json["audits"]["interactive"]["numericValue"]
-                    .as_f64()
-                    .unwrap_or(0.0) / 1000.0;
-                let tbt = json["audits"]["total-blocking-time"]["numericValue"]
-                    .as_f64()
-                    .unwrap_or(0.0) / 1000.0;
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let Some(report) = parse_report_filename(name).filter(|report| report.date >= since && report.env == env) else {
+            continue;
+        };
+        let json = match ReportCache::global().load(&path, max_report_bytes) {
+            Ok(json) => json,
+            Err(e) => {
+                warn!("⚠️ Skipping {}: {}", name, e);
+                continue;
+            }
+        };
+        // Reports of one scenario against several URLs stay apart, named like their summary entries.
+        let url = requested_url(&json);
+        let scenario = match report.scenario_for(url) {
+            label if label == report.scenario => label.to_string(),
+            label => format!("{} ({})", label, normalize_url(url)),
+        };
+        let row = match scenario_row(&scenario, &json, audits) {
+            Ok(row) => row,
+            Err(e) => {
+                warn!("⚠️ Skipping {}: {}", name, e);
+                continue;
+            }
+        };
+        rows.push(ReportRow { scenario, timestamp: report.timestamp.to_string(), date: report.date, row });
+    }
+    Ok(rows)
+}
+
+/// Prints a tabular summary of today's Lighthouse JSON reports in `out_dir` from
+/// environment `env`, plain or gzipped (`.json.gz`). A scenario that ran several times
+/// today is shown with its latest report.
+///
+/// Reports larger than `max_report_bytes` are skipped with a warning.
+pub fn summarize_local_json_reports(
+    out_dir: &Path,
+    env: Option<&str>,
+    max_report_bytes: u64,
+    audits: &AuditMap,
+) -> Result<(), Box<dyn Error>> {
+    status!("\n=== Performance Summary Table ===");
+
+    let today = Local::now().date_naive();
+    let mut latest: BTreeMap<String, ReportRow> = BTreeMap::new();
+    for report in load_report_rows(out_dir, today, env, max_report_bytes, audits)? {
+        if report.date != today {
+            continue;
+        }
+        match latest.get(&report.scenario) {
+            Some(newer) if newer.timestamp >= report.timestamp => {}
+            _ => {
+                latest.insert(report.scenario.clone(), report);
+            }
+        }
+    }
+    let mut rows: Vec<ScenarioMetrics> = latest.into_values().map(|report| report.row).collect();
+    if rows.is_empty() {
+        status!("No reports from today in {}.", out_dir.display());
+        return Ok(());
+    }
 
-                println!(
-                    "{:<18} | Perf: {:>5.1} | FCP: {:>4.2}s | LCP: {:>4.2}s | TTI: {:>4.2}s | TBT: {:>4.2}s",
-                    scenario, perf, fcp, lcp, tti, tbt
-                );
+    let has_baseline = rank_against_baseline(&mut rows);
+    status!("{}", summary_table(&rows, has_baseline, output::use_color()));
+
+    Ok(())
+}
+
+/// Renders the summary rows as a table, with the Core Web Vitals cells colored by
+/// their [`LighthouseMetrics::classify`] rating when `color` is set.
+fn summary_table(rows: &[ScenarioMetrics], has_baseline: bool, color: bool) -> Table {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL_CONDENSED);
+    if color {
+        table.enforce_styling();
+    } else {
+        table.force_no_tty();
+    }
+
+    let mut header = vec!["Scenario", "Perf"];
+    if has_baseline {
+        header.push("ΔPerf");
+    }
+    header.extend(["FCP", "LCP", "TTI", "TBT", "CLS"]);
+    table.set_header(header);
+
+    for s in rows {
+        let cwv = LighthouseMetrics {
+            largest_contentful_paint: s.lcp,
+            total_blocking_time: s.tbt,
+            cumulative_layout_shift: s.cls,
+            ..Default::default()
+        };
+        let ratings = cwv.classify();
+        let rated = |metric: &str, text: String| {
+            let cell = Cell::new(text).set_alignment(CellAlignment::Right);
+            match ratings.iter().find(|(name, _)| *name == metric) {
+                Some((_, rating)) if color => cell.fg(match rating {
+                    Rating::Good => Color::Green,
+                    Rating::NeedsImprovement => Color::Yellow,
+                    Rating::Poor => Color::Red,
+                }),
+                _ => cell,
             }
+        };
+        let number = |text: String| Cell::new(text).set_alignment(CellAlignment::Right);
+
+        let mut row = vec![Cell::new(&s.name), number(format!("{:.1}", s.perf_score))];
+        if has_baseline {
+            row.push(number(format!("{:+.1}", s.delta_perf)));
+        }
+        let format = LighthouseMetrics::format_field;
+        row.extend([
+            number(format("first_contentful_paint", s.fcp)),
+            rated("LCP", format("largest_contentful_paint", s.lcp)),
+            number(format("time_to_interactive", s.tti)),
+            rated("TBT", format("total_blocking_time", s.tbt)),
+            rated("CLS", format("cumulative_layout_shift", s.cls)),
+        ]);
+        table.add_row(row);
+    }
+    table
+}
+
+/// Prints per-scenario daily averages of every report in `out_dir` from environment
+/// `env` dated on or after `since`, oldest day first, for spotting week-over-week trends.
+pub fn summarize_reports_since(
+    out_dir: &Path,
+    since: NaiveDate,
+    env: Option<&str>,
+    max_report_bytes: u64,
+    audits: &AuditMap,
+) -> Result<(), Box<dyn Error>> {
+    status!("\n=== Daily Averages since {} ===", since);
+
+    let mut days: BTreeMap<String, BTreeMap<NaiveDate, Vec<ScenarioMetrics>>> = BTreeMap::new();
+    for report in load_report_rows(out_dir, since, env, max_report_bytes, audits)? {
+        days.entry(report.scenario).or_default().entry(report.date).or_default().push(report.row);
+    }
+    if days.is_empty() {
+        status!("No reports found since {}.", since);
+    }
+
+    for (scenario, by_day) in &days {
+        status!("{}:", scenario);
+        for (date, rows) in by_day {
+            let n = rows.len() as f64;
+            let mean = |field: fn(&ScenarioMetrics) -> f64| rows.iter().map(field).sum::<f64>() / n;
+            let format = LighthouseMetrics::format_field;
+            status!(
+                "  {} ({} report{}) | Perf: {:>5.1} | FCP: {:>6} | LCP: {:>6} | TTI: {:>6} | TBT: {:>6}",
+                date,
+                rows.len(),
+                if rows.len() == 1 { "" } else { "s" },
+                mean(|r| r.perf_score),
+                format("first_contentful_paint", mean(|r| r.fcp)),
+                format("largest_contentful_paint", mean(|r| r.lcp)),
+                format("time_to_interactive", mean(|r| r.tti)),
+                format("total_blocking_time", mean(|r| r.tbt))
+            );
         }
     }
 
@@ -102,11 +521,740 @@ pub fn summarize_local_json_reports() -> Result<(), Box<dyn Error>> {
 }
 
 /// Appends an entry to `summary.json` safely (alias for update_summary).
-pub fn append_to_summary_json(
+pub fn append_to_summary_json(entry: &SummaryEntry, output: &OutputConfig) -> io::Result<()> {
+    update_summary(entry, output)
+}
+
+/// The change in one metric between the previous summary entry and the current run.
+#[derive(Debug, Clone)]
+pub struct MetricDelta {
+    pub metric: String,
+    pub previous: f64,
+    pub current: f64,
+}
+
+impl MetricDelta {
+    pub fn delta(&self) -> f64 {
+        self.current - self.previous
+    }
+
+    /// The change relative to the previous value, or `None` when that was zero.
+    pub fn percent(&self) -> Option<f64> {
+        (self.previous != 0.0).then(|| self.delta() / self.previous * 100.0)
+    }
+
+    /// Scores are better when higher; every other metric is better when lower.
+    pub fn is_regression(&self) -> bool {
+        if self.metric.ends_with("_score") {
+            self.delta() < 0.0
+        } else {
+            self.delta() > 0.0
+        }
+    }
+}
+
+/// Compares `metrics` against the most recent `summary.json` entry for the same
+/// scenario, URL and environment. Returns `None` when there is no prior entry to compare with.
+///
+/// Call this before appending the current run, otherwise it compares the run with itself.
+pub fn compare_with_previous(
+    out_dir: &Path,
     scenario: &str,
     url: &str,
-    fetch_time: &str,
+    env: Option<&str>,
     metrics: &LighthouseMetrics,
-) -> io::Result<()> {
-    update_summary(scenario, url, fetch_time, metrics)
+) -> io::Result<Option<Vec<MetricDelta>>> {
+    let entries = load_summary_entries(&summary_path(out_dir))?;
+
+    let previous = entries
+        .iter()
+        .rev()
+        .find(|e| {
+            e["scenario"].as_str() == Some(scenario) && same_url(e["url"].as_str().unwrap_or_default(), url) && same_env(e, env)
+        });
+
+    let Some(previous) = previous.and_then(|e| e["metrics"].as_object()) else {
+        return Ok(None);
+    };
+
+    Ok(Some(deltas_since(previous, metrics)))
+}
+
+/// Pairs every numeric metric recorded in an entry's `metrics` object with the same metric of `metrics`.
+pub(crate) fn deltas_since(previous: &serde_json::Map<String, Value>, metrics: &LighthouseMetrics) -> Vec<MetricDelta> {
+    let current = serde_json::to_value(metrics).unwrap_or_default();
+    previous
+        .iter()
+        .filter_map(|(metric, value)| {
+            Some(MetricDelta { metric: metric.clone(), previous: value.as_f64()?, current: current[metric].as_f64()? })
+        })
+        .collect()
+}
+
+/// Groups summary entries by scenario, keeping each group in file (chronological) order.
+pub fn group_by_scenario(entries: &[Value]) -> BTreeMap<String, Vec<&Value>> {
+    let mut groups: BTreeMap<String, Vec<&Value>> = BTreeMap::new();
+    for entry in entries {
+        if let Some(scenario) = entry["scenario"].as_str() {
+            groups.entry(scenario.to_string()).or_default().push(entry);
+        }
+    }
+    groups
+}
+
+/// Mean, standard deviation and range accumulated one value at a time (Welford's algorithm).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunningStats {
+    count: usize,
+    mean: f64,
+    m2: f64,
+    min: f64,
+    max: f64,
+}
+
+impl RunningStats {
+    pub fn push(&mut self, value: f64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Smallest value pushed; 0.0 when empty.
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    /// Largest value pushed; 0.0 when empty.
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    /// Sample standard deviation; 0.0 with fewer than two values.
+    pub fn std_dev(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            (self.m2 / (self.count - 1) as f64).sqrt()
+        }
+    }
+}
+
+/// A performance score that fell well below a scenario's recent history.
+#[derive(Debug, Clone)]
+pub struct Regression {
+    pub scenario: String,
+    pub current: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+    /// How many recent runs the mean and standard deviation cover.
+    pub window: usize,
+}
+
+impl Regression {
+    /// How many standard deviations below the mean the current score is.
+    pub fn sigmas(&self) -> f64 {
+        (self.mean - self.current) / self.std_dev
+    }
+}
+
+/// Flags `current_score` as a regression when it is more than `k` standard deviations
+/// below the mean of the last `n` `summary.json` entries for `scenario` at `url`.
+///
+/// Needs at least two prior runs. Call this before appending the current run.
+pub fn detect_regression(
+    out_dir: &Path,
+    scenario: &str,
+    url: &str,
+    env: Option<&str>,
+    current_score: f64,
+    n: usize,
+    k: f64,
+) -> io::Result<Option<Regression>> {
+    let entries = load_summary_entries(&summary_path(out_dir))?;
+    let groups = group_by_scenario(&entries);
+    let Some(history) = groups.get(scenario) else {
+        return Ok(None);
+    };
+
+    let mut stats = RunningStats::default();
+    let same_series = |e: &&&Value| same_url(e["url"].as_str().unwrap_or_default(), url) && same_env(e, env);
+    for entry in history.iter().rev().filter(same_series).take(n) {
+        if let Some(score) = entry["metrics"]["performance_score"].as_f64() {
+            stats.push(score);
+        }
+    }
+
+    if stats.count() < 2 || current_score.is_nan() || current_score >= stats.mean() - k * stats.std_dev() {
+        return Ok(None);
+    }
+
+    Ok(Some(Regression {
+        scenario: scenario.to_string(),
+        current: current_score,
+        mean: stats.mean(),
+        std_dev: stats.std_dev(),
+        window: stats.count(),
+    }))
+}
+
+/// Prints changed metrics, regressions in red and improvements in green.
+pub fn print_deltas(deltas: &[MetricDelta]) {
+    for d in deltas.iter().filter(|d| d.delta().abs() > f64::EPSILON) {
+        let line = format!("- {}: {:.2} -> {:.2} ({:+.2})", d.metric, d.previous, d.current, d.delta());
+        if d.is_regression() {
+            info!("{}", line.red());
+        } else {
+            info!("{}", line.green());
+        }
+    }
+}
+
+/// Metric changes smaller than this percentage are treated as run-to-run noise when
+/// [`ScenarioChange::worse_and_better`] tallies them.
+pub const COMPARISON_NOISE_PERCENT: f64 = 5.0;
+
+/// A mean performance score change within this many points is a [`Verdict::Unchanged`].
+pub const VERDICT_THRESHOLD_POINTS: f64 = 1.0;
+
+/// The overall direction of a [`SummaryComparison`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Regression,
+    Improvement,
+    Unchanged,
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Verdict::Regression => "net regression",
+            Verdict::Improvement => "net improvement",
+            Verdict::Unchanged => "no net change",
+        })
+    }
+}
+
+/// How one scenario's latest entry changed between two summary files.
+#[derive(Debug, Clone)]
+pub struct ScenarioChange {
+    /// The scenario label, with `@<env>` for entries recorded under `--env-label`.
+    pub scenario: String,
+    /// Every metric present in both entries, in field order, in seconds.
+    pub deltas: Vec<MetricDelta>,
+}
+
+impl ScenarioChange {
+    pub fn delta(&self, metric: &str) -> Option<&MetricDelta> {
+        self.deltas.iter().find(|d| d.metric == metric)
+    }
+
+    /// How many metrics got worse and how many got better by more than [`COMPARISON_NOISE_PERCENT`].
+    pub fn worse_and_better(&self) -> (usize, usize) {
+        let significant = self
+            .deltas
+            .iter()
+            .filter(|d| d.percent().is_some_and(|p| p.abs() > COMPARISON_NOISE_PERCENT));
+        significant.fold((0, 0), |(worse, better), d| {
+            if d.is_regression() {
+                (worse + 1, better)
+            } else {
+                (worse, better + 1)
+            }
+        })
+    }
+}
+
+/// Two `summary.json` files compared scenario by scenario; see [`compare_summaries`].
+#[derive(Debug, Clone, Default)]
+pub struct SummaryComparison {
+    pub changes: Vec<ScenarioChange>,
+    /// Scenarios only in the older file.
+    pub removed: Vec<String>,
+    /// Scenarios only in the newer file.
+    pub added: Vec<String>,
+}
+
+impl SummaryComparison {
+    /// The mean performance score change across the matched scenarios, in points.
+    pub fn mean_score_change(&self) -> Option<f64> {
+        let changes: Vec<f64> = self
+            .changes
+            .iter()
+            .filter_map(|change| change.delta("performance_score"))
+            .map(MetricDelta::delta)
+            .filter(|delta| !delta.is_nan())
+            .collect();
+        (!changes.is_empty()).then(|| changes.iter().sum::<f64>() / changes.len() as f64)
+    }
+
+    /// A regression or improvement when the mean performance score moved by more than
+    /// [`VERDICT_THRESHOLD_POINTS`].
+    pub fn verdict(&self) -> Verdict {
+        match self.mean_score_change() {
+            Some(change) if change < -VERDICT_THRESHOLD_POINTS => Verdict::Regression,
+            Some(change) if change > VERDICT_THRESHOLD_POINTS => Verdict::Improvement,
+            _ => Verdict::Unchanged,
+        }
+    }
+}
+
+/// The latest entry of each scenario in the summary file at `path`, keyed as in [`ScenarioChange::scenario`].
+fn latest_entries(path: &Path) -> io::Result<BTreeMap<String, Value>> {
+    if !path.exists() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", path.display())));
+    }
+    let fetch_time = |entry: &Value| {
+        let time = entry["fetch_time"].as_str().unwrap_or_default().to_string();
+        (DateTime::parse_from_rfc3339(&time).ok(), time)
+    };
+    let mut latest: BTreeMap<String, Value> = BTreeMap::new();
+    for entry in load_summary_entries(path)? {
+        let Some(scenario) = entry["scenario"].as_str() else {
+            continue;
+        };
+        let key = match entry["env"].as_str() {
+            Some(env) => format!("{}{}{}", scenario, ENV_SEPARATOR, env),
+            None => scenario.to_string(),
+        };
+        match latest.get(&key) {
+            Some(newer) if fetch_time(newer) > fetch_time(&entry) => {}
+            _ => {
+                latest.insert(key, entry);
+            }
+        }
+    }
+    Ok(latest)
+}
+
+/// Compares the latest entry of every scenario in the `before` summary file with the
+/// latest entry of the same scenario (and environment) in `after`, e.g. the summaries of
+/// two releases.
+pub fn compare_summaries(before: &Path, after: &Path) -> io::Result<SummaryComparison> {
+    let before = latest_entries(before)?;
+    let mut after = latest_entries(after)?;
+    let mut comparison = SummaryComparison::default();
+    for (scenario, old) in before {
+        match after.remove(&scenario) {
+            Some(new) => {
+                let deltas = diff_metric_values(&old["metrics"], &new["metrics"]);
+                comparison.changes.push(ScenarioChange { scenario, deltas });
+            }
+            None => comparison.removed.push(scenario),
+        }
+    }
+    comparison.added = after.into_keys().collect();
+    Ok(comparison)
+}
+
+/// Prints a [`SummaryComparison`]: the change in the headline metrics per scenario,
+/// how many metrics moved beyond noise, and the overall verdict.
+pub fn print_summary_comparison(comparison: &SummaryComparison) {
+    const COLUMNS: [(&str, &str); 6] = [
+        ("Perf", "performance_score"),
+        ("FCP", "first_contentful_paint"),
+        ("LCP", "largest_contentful_paint"),
+        ("TBT", "total_blocking_time"),
+        ("CLS", "cumulative_layout_shift"),
+        ("INP", "interaction_to_next_paint"),
+    ];
+    let color = output::use_color();
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL_CONDENSED);
+    if color {
+        table.enforce_styling();
+    } else {
+        table.force_no_tty();
+    }
+    let mut header = vec!["Scenario"];
+    header.extend(COLUMNS.map(|(title, _)| title));
+    header.extend(["Worse", "Better"]);
+    table.set_header(header);
+
+    for change in &comparison.changes {
+        let mut row = vec![Cell::new(&change.scenario)];
+        for (_, metric) in COLUMNS {
+            let cell = match change.delta(metric) {
+                Some(d) => {
+                    let text = LighthouseMetrics::format_field(metric, d.delta());
+                    let cell = Cell::new(if d.delta() > 0.0 { format!("+{}", text) } else { text });
+                    let significant = d.percent().is_some_and(|p| p.abs() > COMPARISON_NOISE_PERCENT);
+                    match (significant && color, d.is_regression()) {
+                        (true, true) => cell.fg(Color::Red),
+                        (true, false) => cell.fg(Color::Green),
+                        _ => cell,
+                    }
+                }
+                None => Cell::new("n/a"),
+            };
+            row.push(cell.set_alignment(CellAlignment::Right));
+        }
+        let (worse, better) = change.worse_and_better();
+        row.push(Cell::new(worse).set_alignment(CellAlignment::Right));
+        row.push(Cell::new(better).set_alignment(CellAlignment::Right));
+        table.add_row(row);
+    }
+    status!("{}", table);
+
+    if !comparison.removed.is_empty() {
+        status!("Only in the older summary: {}", comparison.removed.join(", "));
+    }
+    if !comparison.added.is_empty() {
+        status!("Only in the newer summary: {}", comparison.added.join(", "));
+    }
+    match comparison.mean_score_change() {
+        Some(change) => status!(
+            "Verdict: {} (mean performance score {:+.1} points across {} scenario{})",
+            comparison.verdict(),
+            change,
+            comparison.changes.len(),
+            if comparison.changes.len() == 1 { "" } else { "s" }
+        ),
+        None => status!("Verdict: {} (no scenario appears in both summaries)", comparison.verdict()),
+    }
+}
+
+/// Every metric of `before` paired with the same metric of `after`, in field order.
+pub fn diff_metrics(before: &LighthouseMetrics, after: &LighthouseMetrics) -> Vec<MetricDelta> {
+    let (before, after) = (serde_json::to_value(before).unwrap_or_default(), serde_json::to_value(after).unwrap_or_default());
+    diff_metric_values(&before, &after)
+}
+
+/// Like [`diff_metrics`], for metrics as stored in `summary.json`; fields missing on
+/// either side are left out.
+fn diff_metric_values(before: &Value, after: &Value) -> Vec<MetricDelta> {
+    LighthouseMetrics::FIELD_NAMES
+        .iter()
+        .filter_map(|&field| {
+            Some(MetricDelta {
+                metric: field.to_string(),
+                previous: before[field].as_f64()?,
+                current: after[field].as_f64()?,
+            })
+        })
+        .collect()
+}
+
+/// Prints a table of every metric's absolute and percentage change, with an arrow
+/// showing the direction and colour showing whether it regressed or improved.
+pub fn print_diff_table(deltas: &[MetricDelta]) {
+    status!(
+        "{:<38} {:>12} {:>12} {:>12} {:>9}",
+        "Metric", "Before", "After", "Change", "%"
+    );
+    for d in deltas {
+        let arrow = if d.delta() > 0.0 {
+            "↑"
+        } else if d.delta() < 0.0 {
+            "↓"
+        } else {
+            "="
+        };
+        let percent = d.percent().map(|p| format!("{:+.1}%", p)).unwrap_or_else(|| "n/a".to_string());
+        let line = format!(
+            "{} {:<36} {:>12.2} {:>12.2} {:>+12.2} {:>9}",
+            arrow, d.metric, d.previous, d.current, d.delta(), percent
+        );
+        if d.delta().abs() <= f64::EPSILON || d.delta().is_nan() {
+            status!("{}", line);
+        } else if d.is_regression() {
+            status!("{}", line.red());
+        } else {
+            status!("{}", line.green());
+        }
+    }
+}
+
+/// Flattens `summary.json` in `out_dir` into a CSV at `path`: one row per entry
+/// with scenario, url, env, fetch_time, then every `LighthouseMetrics` field in order.
+pub fn export_csv(out_dir: &Path, path: &Path) -> Result<(), Box<dyn Error>> {
+    let entries = load_summary_entries(&summary_path(out_dir))?;
+    let mut writer = csv::Writer::from_path(path)?;
+
+    let mut header = vec!["scenario", "url", "env", "fetch_time"];
+    header.extend(LighthouseMetrics::FIELD_NAMES);
+    writer.write_record(&header)?;
+
+    for entry in &entries {
+        let mut row = vec![
+            entry["scenario"].as_str().unwrap_or_default().to_string(),
+            entry["url"].as_str().unwrap_or_default().to_string(),
+            entry["env"].as_str().unwrap_or_default().to_string(),
+            entry["fetch_time"].as_str().unwrap_or_default().to_string(),
+        ];
+        for field in LighthouseMetrics::FIELD_NAMES {
+            row.push(
+                entry["metrics"][field]
+                    .as_f64()
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            );
+        }
+        writer.write_record(&row)?;
+    }
+
+    writer.flush()?;
+    info!("CSV export written to {} ({} entries)", path.display(), entries.len());
+    Ok(())
+}
+
+/// How one metric changed between a run roughly `period` ago and the latest run.
+#[derive(Debug, Clone)]
+pub struct PeriodDelta {
+    pub metric: String,
+    /// When the earlier run actually happened (the nearest available to the target date).
+    pub from_time: DateTime<Utc>,
+    pub to_time: DateTime<Utc>,
+    pub from_value: f64,
+    pub to_value: f64,
+    pub absolute: f64,
+    /// Relative change in percent; `None` when the earlier value was zero.
+    pub percent: Option<f64>,
+}
+
+/// Compares the latest `metric` value for `scenario` against the run nearest to
+/// `now - period`. The earlier run may be up to a quarter of `period` away from
+/// the target date; see [`period_comparison_within`] to choose the tolerance.
+pub fn period_comparison(
+    out_dir: &Path,
+    scenario: &str,
+    env: Option<&str>,
+    metric: &str,
+    period: Duration,
+) -> io::Result<Option<PeriodDelta>> {
+    period_comparison_within(out_dir, scenario, env, metric, period, period / 4)
+}
+
+/// Like [`period_comparison`], with an explicit tolerance around the target date.
+pub fn period_comparison_within(
+    out_dir: &Path,
+    scenario: &str,
+    env: Option<&str>,
+    metric: &str,
+    period: Duration,
+    tolerance: Duration,
+) -> io::Result<Option<PeriodDelta>> {
+    let entries = load_summary_entries(&summary_path(out_dir))?;
+
+    let mut runs: Vec<(DateTime<Utc>, f64)> = entries
+        .iter()
+        .filter(|e| e["scenario"].as_str() == Some(scenario) && same_env(e, env))
+        .filter_map(|e| {
+            let time = DateTime::parse_from_rfc3339(e["fetch_time"].as_str()?).ok()?;
+            Some((time.with_timezone(&Utc), e["metrics"][metric].as_f64()?))
+        })
+        .collect();
+    runs.sort_by_key(|(time, _)| *time);
+
+    let Some(&(to_time, to_value)) = runs.last() else {
+        return Ok(None);
+    };
+
+    let target = Utc::now() - period;
+    let nearest = runs
+        .iter()
+        .filter(|(time, _)| *time < to_time)
+        .min_by_key(|(time, _)| (*time - target).abs());
+
+    let Some(&(from_time, from_value)) = nearest.filter(|(time, _)| (*time - target).abs() <= tolerance) else {
+        return Ok(None);
+    };
+
+    let absolute = to_value - from_value;
+    Ok(Some(PeriodDelta {
+        metric: metric.to_string(),
+        from_time,
+        to_time,
+        from_value,
+        to_value,
+        absolute,
+        percent: (from_value != 0.0).then(|| absolute / from_value * 100.0),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn entry(scenario: &str) -> SummaryEntry {
+        SummaryEntry {
+            schema_version: SUMMARY_SCHEMA_VERSION,
+            scenario: scenario.to_string(),
+            url: "https://example.com".to_string(),
+            final_url: "https://example.com/".to_string(),
+            fetch_time: Utc::now().to_rfc3339(),
+            cache_state: "cold".to_string(),
+            env: None,
+            blocked_patterns: Vec::new(),
+            metadata: RunMetadata::default(),
+            metrics: LighthouseMetrics::default(),
+            dom: DomStats::default(),
+        }
+    }
+
+    #[test]
+    fn malformed_summary_is_backed_up_not_discarded() {
+        let out_dir = TempDir::new("summary");
+        let output = OutputConfig { out_dir: out_dir.to_path_buf(), ..Default::default() };
+        let corrupt = r#"[{"scenario": "baseline", "metrics": {"#;
+        fs::write(summary_path(&out_dir), corrupt).unwrap();
+
+        update_summary(&entry("baseline"), &output).unwrap();
+
+        let entries = load_summary_entries(&summary_path(&out_dir)).unwrap();
+        let backups: Vec<PathBuf> = fs::read_dir(&out_dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.to_string_lossy().contains("summary.json.corrupt-"))
+            .collect();
+        let backup_contents = backups.first().map(|p| fs::read_to_string(p).unwrap());
+        let leftover_tmp = out_dir.join("summary.json.tmp").exists();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backup_contents.as_deref(), Some(corrupt));
+        assert!(!leftover_tmp);
+    }
+
+    #[test]
+    fn csv_metric_columns_follow_declaration_order() {
+        let out_dir = TempDir::new("csv");
+        let output = OutputConfig { out_dir: out_dir.to_path_buf(), ..Default::default() };
+        let mut baseline = entry("baseline");
+        baseline.metrics.performance_score = 91.0;
+        update_summary(&baseline, &output).unwrap();
+
+        let csv_path = out_dir.join("summary.csv");
+        export_csv(&out_dir, &csv_path).unwrap();
+        let csv = fs::read_to_string(&csv_path).unwrap();
+
+        let mut lines = csv.lines();
+        let header: Vec<&str> = lines.next().unwrap().split(',').collect();
+        let row: Vec<&str> = lines.next().unwrap().split(',').collect();
+        assert_eq!(header[4..], LighthouseMetrics::FIELD_NAMES);
+        let score = header.iter().position(|&h| h == "performance_score").unwrap();
+        assert_eq!(row[score], "91");
+    }
+
+    #[test]
+    fn merge_dedupes_and_sorts_shards() {
+        let dir = TempDir::new("merge");
+        let shard = |name: &str, times: &[&str]| {
+            let entries: Vec<Value> = times
+                .iter()
+                .map(|t| serde_json::json!({ "scenario": "baseline", "url": "https://example.com", "fetch_time": t }))
+                .collect();
+            let path = dir.join(name);
+            fs::write(&path, serde_json::to_string(&entries).unwrap()).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        let a = shard("a.json", &["2024-01-03T00:00:00Z", "2024-01-01T00:00:00Z"]);
+        let b = shard("b.json", &["2024-01-02T00:00:00Z", "2024-01-01T00:00:00Z"]);
+        let empty = dir.join("empty.json");
+        fs::write(&empty, "").unwrap();
+        let empty = empty.to_string_lossy().into_owned();
+        let missing = dir.join("missing.json").to_string_lossy().into_owned();
+        let out = dir.join("summary.json").to_string_lossy().into_owned();
+
+        let count = merge(&[&a, &b, &empty, &missing], &out).unwrap();
+        let merged = load_summary_entries(Path::new(&out)).unwrap();
+
+        let times: Vec<&str> = merged.iter().map(|e| e["fetch_time"].as_str().unwrap()).collect();
+        assert_eq!(count, 3);
+        assert_eq!(times, ["2024-01-01T00:00:00Z", "2024-01-02T00:00:00Z", "2024-01-03T00:00:00Z"]);
+    }
+
+    #[test]
+    fn unversioned_entries_are_migrated_on_read() {
+        let out_dir = TempDir::new("summary");
+        let legacy = r#"[{"scenario": "baseline", "url": "https://example.com", "fetch_time": "2024-01-01T00:00:00Z",
+            "metrics": {"performance_score": 91.0}}]"#;
+        fs::write(summary_path(&out_dir), legacy).unwrap();
+
+        let entries = load_summary_entries(&summary_path(&out_dir)).unwrap();
+
+        let entry = &entries[0];
+        assert_eq!(entry["schema_version"], SUMMARY_SCHEMA_VERSION);
+        assert_eq!(entry["metrics"]["performance_score"], 91.0);
+        assert_eq!(entry["metrics"]["interaction_to_next_paint"], 0.0);
+        assert_eq!(entry["final_url"], "https://example.com");
+        assert_eq!(entry["cache_state"], "cold");
+        assert_eq!(entry["dom"]["max_depth"], 0);
+    }
+
+    #[test]
+    fn summaries_compare_latest_entries_per_scenario() {
+        let dir = TempDir::new("compare");
+        let entry = |scenario: &str, time: &str, score: f64, lcp: f64| {
+            serde_json::json!({
+                "schema_version": SUMMARY_SCHEMA_VERSION, "scenario": scenario, "fetch_time": time,
+                "metrics": { "performance_score": score, "largest_contentful_paint": lcp },
+            })
+        };
+        let old = serde_json::json!([
+            entry("baseline", "2024-01-02T00:00:00Z", 90.0, 2.0),
+            entry("baseline", "2024-01-01T00:00:00Z", 50.0, 9.0),
+            entry("no-appd", "2024-01-01T00:00:00Z", 80.0, 2.5),
+        ]);
+        let new = serde_json::json!([
+            entry("baseline", "2024-02-01T00:00:00Z", 85.0, 2.4),
+            entry("no-quantum", "2024-02-01T00:00:00Z", 70.0, 3.0),
+        ]);
+        fs::write(dir.join("old.json"), old.to_string()).unwrap();
+        fs::write(dir.join("new.json"), new.to_string()).unwrap();
+
+        let comparison = compare_summaries(&dir.join("old.json"), &dir.join("new.json")).unwrap();
+        let missing = compare_summaries(&dir.join("old.json"), &dir.join("missing.json"));
+
+        assert_eq!(comparison.changes.len(), 1);
+        let baseline = &comparison.changes[0];
+        assert_eq!(baseline.delta("performance_score").unwrap().delta(), -5.0);
+        assert_eq!(baseline.worse_and_better(), (2, 0));
+        assert_eq!(comparison.removed, ["no-appd"]);
+        assert_eq!(comparison.added, ["no-quantum"]);
+        assert_eq!(comparison.verdict(), Verdict::Regression);
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn report_filenames_match_timestamped_and_date_only_names() {
+        let day = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+
+        let report = parse_report_filename("lighthouse_report_baseline-warm@staging_2024-01-01T09-30-00.json.gz").unwrap();
+        assert_eq!(report.scenario, "baseline-warm");
+        assert_eq!(report.env, Some("staging"));
+        assert_eq!(report.timestamp, "2024-01-01T09-30-00");
+        assert_eq!(report.date, day);
+
+        let legacy = parse_report_filename("lighthouse_report_no-tealium_2024-01-01.json").unwrap();
+        assert_eq!((legacy.scenario, legacy.date), ("no-tealium", day));
+        assert!(legacy.timestamp < report.timestamp);
+
+        let per_url = parse_report_filename("lighthouse_report_home_page_example-com-shop_2024-01-01T09-30-00.json").unwrap();
+        assert_eq!(per_url.scenario_for("https://example.com/shop/"), "home_page");
+        assert_eq!(per_url.scenario_for("https://example.org"), "home_page_example-com-shop");
+        assert_eq!(report.scenario_for("https://example.com"), "baseline-warm");
+
+        assert!(parse_report_filename("lighthouse_report_baseline_yesterday.json").is_none());
+        assert!(parse_report_filename("lighthouse_report_baseline_2024-01-01T09-30-00-0.trace.json").is_none());
+        assert!(crate::config::validate_timestamp_format("%Y-%m-%dT%H-%M-%S").is_ok());
+        assert!(crate::config::validate_timestamp_format("%H-%M-%S").is_err());
+        assert!(crate::config::validate_timestamp_format("%Y-%m-%d_%H").is_err());
+    }
 }
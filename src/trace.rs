@@ -1,23 +1,230 @@
-use std::fs;
+use std::collections::HashMap;
+use std::path::Path;
+use log::info;
+use serde::Serialize;
 use serde_json::Value;
 
-pub fn parse_trace_json(trace_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let data = fs::read_to_string(trace_path)?;
-    let json: Value = serde_json::from_str(&data)?;
-    if let Some(events) = json.get("traceEvents").and_then(|v| v.as_array()) {
-        let mut times = vec![];
-        for e in events {
-            if e.get("name") == Some(&Value::String("RunTask".to_string())) {
-                if let Some(dur) = e.get("dur").and_then(|d| d.as_u64()) {
-                    times.push(dur as f64 / 1000.0);
-                }
+use crate::lighthouse::parse_report_file;
+
+/// Trace event durations (`dur`) are recorded in microseconds.
+pub const MICROS_PER_MILLI: f64 = 1000.0;
+
+/// Main-thread activity extracted from a trace's `RunTask` events.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TraceSummary {
+    /// Number of entries in `traceEvents`; 0 means the file had no events at all.
+    pub event_count: usize,
+    /// Every `RunTask` duration in milliseconds, longest first.
+    pub long_tasks: Vec<f64>,
+    /// Sum of all `RunTask` durations in milliseconds.
+    pub total_main_thread_ms: f64,
+    /// Number of `RunTask` events in the trace.
+    pub task_count: usize,
+    /// Network requests with both a start and finish event, slowest first.
+    pub resources: Vec<ResourceTiming>,
+    /// Time spent per event name (`Layout`, `UpdateLayoutTree`, `Paint`, ...), most expensive first.
+    pub categories: Vec<EventCategory>,
+}
+
+/// Total time and occurrences of one kind of trace event.
+///
+/// Events nest (a `Layout` runs inside a `RunTask`), so totals across categories overlap.
+#[derive(Debug, Clone, Serialize)]
+pub struct EventCategory {
+    pub name: String,
+    pub total_ms: f64,
+    pub count: usize,
+}
+
+/// Sums the durations of every timed event by `name`, leaving out the `RunTask`
+/// wrapper that is already reported separately.
+fn extract_event_categories(events: &[Value]) -> Vec<EventCategory> {
+    let mut totals: HashMap<&str, (f64, usize)> = HashMap::new();
+    for e in events {
+        let (Some(name), Some(dur)) = (e["name"].as_str(), e["dur"].as_f64()) else {
+            continue;
+        };
+        if name == "RunTask" {
+            continue;
+        }
+        let total = totals.entry(name).or_default();
+        total.0 += dur / MICROS_PER_MILLI;
+        total.1 += 1;
+    }
+
+    let mut categories: Vec<EventCategory> = totals
+        .into_iter()
+        .map(|(name, (total_ms, count))| EventCategory { name: name.to_string(), total_ms, count })
+        .collect();
+    categories.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+    categories
+}
+
+/// Timing of one network request reconstructed from the trace's resource events.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceTiming {
+    pub request_id: String,
+    pub url: String,
+    /// From `ResourceSendRequest` to `ResourceFinish`, in milliseconds.
+    pub duration_ms: f64,
+    /// From `ResourceSendRequest` to `ResourceReceiveResponse`, when the response event exists.
+    pub ttfb_ms: Option<f64>,
+}
+
+/// Pairs `ResourceSendRequest`, `ResourceReceiveResponse` and `ResourceFinish`
+/// events by `requestId`. Requests missing a send or finish event are skipped.
+fn extract_resource_timings(events: &[Value]) -> Vec<ResourceTiming> {
+    let mut sent: HashMap<&str, (&str, f64)> = HashMap::new();
+    let mut received: HashMap<&str, f64> = HashMap::new();
+    let mut finished: HashMap<&str, f64> = HashMap::new();
+
+    for e in events {
+        let data = &e["args"]["data"];
+        let (Some(request_id), Some(ts)) = (data["requestId"].as_str(), e["ts"].as_f64()) else {
+            continue;
+        };
+        match e["name"].as_str() {
+            Some("ResourceSendRequest") => {
+                sent.insert(request_id, (data["url"].as_str().unwrap_or("unknown"), ts));
             }
+            Some("ResourceReceiveResponse") => {
+                received.insert(request_id, ts);
+            }
+            Some("ResourceFinish") => {
+                finished.insert(request_id, ts);
+            }
+            _ => {}
+        }
+    }
+
+    let mut resources: Vec<ResourceTiming> = sent
+        .iter()
+        .filter_map(|(request_id, (url, start))| {
+            let end = finished.get(request_id)?;
+            Some(ResourceTiming {
+                request_id: request_id.to_string(),
+                url: url.to_string(),
+                duration_ms: (end - start) / MICROS_PER_MILLI,
+                ttfb_ms: received.get(request_id).map(|ts| (ts - start) / MICROS_PER_MILLI),
+            })
+        })
+        .collect();
+
+    resources.sort_by(|a, b| b.duration_ms.total_cmp(&a.duration_ms));
+    resources
+}
+
+/// Reads a Chrome trace file (at most `max_bytes`) and summarizes it with [`summarize_trace`].
+pub fn parse_trace_json(trace_path: &str, max_bytes: u64) -> Result<TraceSummary, Box<dyn std::error::Error>> {
+    let json = parse_report_file(Path::new(trace_path), max_bytes)?;
+    Ok(summarize_trace(&json))
+}
+
+/// Logs the main-thread tasks, busiest event categories and slowest resources of a trace.
+pub fn print_trace_summary(trace: &TraceSummary) {
+    if trace.event_count == 0 {
+        info!("No traceEvents found.");
+    } else if trace.task_count == 0 {
+        info!("No RunTask events found in trace.");
+    } else {
+        info!(
+            "Main thread: {} tasks, {:.2} ms total",
+            trace.task_count, trace.total_main_thread_ms
+        );
+        info!("Top 5 RunTask durations (ms):");
+        for dur in trace.long_tasks.iter().take(5) {
+            info!("- {:.2} ms", dur);
         }
-        times.sort_by(|a, b| b.partial_cmp(a).unwrap());
-        println!("Top 5 RunTask durations (ms):");
-        for dur in times.iter().take(5) {
-            println!("- {:.2} ms", dur);
+    }
+
+    if !trace.categories.is_empty() {
+        info!("Top 10 trace event categories by total time:");
+        for category in trace.categories.iter().take(10) {
+            info!("- {:<24} {:>9.2} ms ({} events)", category.name, category.total_ms, category.count);
         }
     }
-    Ok(())
+
+    if !trace.resources.is_empty() {
+        info!("Top 10 slowest resources:");
+        for resource in trace.resources.iter().take(10) {
+            let ttfb = resource
+                .ttfb_ms
+                .map(|t| format!(" (TTFB {:.0} ms)", t))
+                .unwrap_or_default();
+            info!("- {:>8.0} ms{}  {}", resource.duration_ms, ttfb, resource.url);
+        }
+    }
+}
+
+/// Summarizes the `traceEvents` of an already-parsed trace.
+pub fn summarize_trace(json: &Value) -> TraceSummary {
+    let Some(events) = json.get("traceEvents").and_then(|v| v.as_array()) else {
+        return TraceSummary::default();
+    };
+
+    let mut times: Vec<f64> = events
+        .iter()
+        .filter(|e| e["name"].as_str() == Some("RunTask"))
+        .filter_map(|e| e["dur"].as_f64())
+        .map(|dur| dur / MICROS_PER_MILLI)
+        .collect();
+    times.sort_by(|a, b| b.total_cmp(a));
+
+    TraceSummary {
+        event_count: events.len(),
+        total_main_thread_ms: times.iter().sum(),
+        task_count: times.len(),
+        long_tasks: times,
+        resources: extract_resource_timings(events),
+        categories: extract_event_categories(events),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn summarizes_synthetic_trace() {
+        let trace = json!({
+            "traceEvents": [
+                { "name": "RunTask", "ph": "X", "ts": 0, "dur": 50_000 },
+                { "name": "RunTask", "ph": "X", "ts": 60_000, "dur": 12_500 },
+                { "name": "Layout", "ph": "X", "ts": 1_000, "dur": 8_000 },
+                { "name": "Layout", "ph": "X", "ts": 61_000, "dur": 2_000 },
+                { "name": "Paint", "ph": "X", "ts": 70_000, "dur": 1_000 },
+                { "name": "ResourceSendRequest", "ts": 100_000,
+                  "args": { "data": { "requestId": "1", "url": "https://example.com/app.js" } } },
+                { "name": "ResourceReceiveResponse", "ts": 130_000, "args": { "data": { "requestId": "1" } } },
+                { "name": "ResourceFinish", "ts": 180_000, "args": { "data": { "requestId": "1" } } },
+                { "name": "ResourceSendRequest", "ts": 100_000,
+                  "args": { "data": { "requestId": "2", "url": "https://example.com/never-finished" } } }
+            ]
+        });
+
+        let summary = summarize_trace(&trace);
+
+        assert_eq!(summary.event_count, 9);
+        assert_eq!(summary.task_count, 2);
+        assert_eq!(summary.long_tasks, vec![50.0, 12.5]);
+        assert_eq!(summary.total_main_thread_ms, 62.5);
+
+        assert_eq!(summary.categories[0].name, "Layout");
+        assert_eq!(summary.categories[0].total_ms, 10.0);
+        assert_eq!(summary.categories[0].count, 2);
+        assert!(summary.categories.iter().all(|c| c.name != "RunTask"));
+
+        assert_eq!(summary.resources.len(), 1);
+        assert_eq!(summary.resources[0].url, "https://example.com/app.js");
+        assert_eq!(summary.resources[0].duration_ms, 80.0);
+        assert_eq!(summary.resources[0].ttfb_ms, Some(30.0));
+    }
+
+    #[test]
+    fn missing_trace_events_is_empty() {
+        let summary = summarize_trace(&json!({}));
+        assert_eq!(summary.event_count, 0);
+        assert_eq!(summary.task_count, 0);
+    }
 }
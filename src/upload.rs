@@ -0,0 +1,135 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Where `--s3` uploads go: a bucket, and a key prefix that may be empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Target {
+    pub bucket: String,
+    pub prefix: String,
+}
+
+impl S3Target {
+    /// The object key a file named `file_name` is uploaded under.
+    pub fn key(&self, file_name: &str) -> String {
+        if self.prefix.is_empty() {
+            file_name.to_string()
+        } else {
+            format!("{}/{}", self.prefix, file_name)
+        }
+    }
+}
+
+/// Parses `bucket` or `bucket/some/prefix`; surrounding slashes are ignored.
+impl FromStr for S3Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix("s3://").unwrap_or(s).trim_matches('/');
+        let (bucket, prefix) = s.split_once('/').unwrap_or((s, ""));
+        if bucket.is_empty() {
+            return Err("expected BUCKET or BUCKET/PREFIX".to_string());
+        }
+        Ok(S3Target { bucket: bucket.to_string(), prefix: prefix.trim_matches('/').to_string() })
+    }
+}
+
+/// The files in `out_dir` this invocation produced: everything named with its
+/// `timestamp` (reports, their HTML and trace assets, summary pages) plus the
+/// cumulative `summary.json`. Directories such as filmstrips are left out.
+pub fn run_artifacts(out_dir: &Path, timestamp: &str) -> io::Result<Vec<PathBuf>> {
+    let mut artifacts = Vec::new();
+    for entry in fs::read_dir(out_dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if path.is_file() && (name.contains(timestamp) || name == "summary.json") {
+            artifacts.push(path);
+        }
+    }
+    artifacts.sort();
+    Ok(artifacts)
+}
+
+/// Uploads `files` to `target`, keyed by file name under its prefix.
+///
+/// Credentials come from `AWS_ACCESS_KEY_ID` / `AWS_SECRET_ACCESS_KEY` (and
+/// `AWS_SESSION_TOKEN`), the region from `AWS_REGION` (default `us-east-1`). Setting
+/// `AWS_ENDPOINT_URL` targets an S3-compatible service such as MinIO instead, with
+/// path-style addressing. A file that fails to upload is logged and skipped; returns
+/// how many were uploaded.
+#[cfg(feature = "s3")]
+pub async fn upload_artifacts(target: &S3Target, files: &[PathBuf]) -> Result<usize, Box<dyn std::error::Error>> {
+    use log::{debug, warn};
+    use s3::creds::Credentials;
+    use s3::{Bucket, Region};
+
+    let region_name = std::env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+    let bucket = match std::env::var("AWS_ENDPOINT_URL") {
+        Ok(endpoint) => {
+            Bucket::new(&target.bucket, Region::Custom { region: region_name, endpoint }, Credentials::from_env()?)?
+                .with_path_style()
+        }
+        Err(_) => Bucket::new(&target.bucket, region_name.parse()?, Credentials::from_env()?)?,
+    };
+
+    let mut uploaded = 0;
+    for path in files {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let key = target.key(name);
+        let content = match fs::read(path) {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("⚠️ Could not read {} for upload: {}", path.display(), e);
+                continue;
+            }
+        };
+        match bucket.put_object_with_content_type(&key, &content, content_type(name)).await {
+            Ok(response) if (200..300).contains(&response.status_code()) => {
+                debug!("Uploaded {} to s3://{}/{}", path.display(), target.bucket, key);
+                uploaded += 1;
+            }
+            Ok(response) => warn!(
+                "⚠️ Upload of {} to s3://{}/{} failed with status {}",
+                path.display(),
+                target.bucket,
+                key,
+                response.status_code()
+            ),
+            Err(e) => warn!("⚠️ Upload of {} to s3://{}/{} failed: {}", path.display(), target.bucket, key, e),
+        }
+    }
+    Ok(uploaded)
+}
+
+#[cfg(feature = "s3")]
+fn content_type(file_name: &str) -> &'static str {
+    match file_name.rsplit('.').next() {
+        Some("json") => "application/json",
+        Some("gz") => "application/gzip",
+        Some("html") => "text/html; charset=utf-8",
+        Some("md") => "text/markdown; charset=utf-8",
+        Some("txt") => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_split_bucket_and_prefix() {
+        let target: S3Target = "s3://perf-artifacts/ci/nightly/".parse().unwrap();
+        assert_eq!(target, S3Target { bucket: "perf-artifacts".to_string(), prefix: "ci/nightly".to_string() });
+        assert_eq!(target.key("summary.json"), "ci/nightly/summary.json");
+
+        let bare: S3Target = "perf-artifacts".parse().unwrap();
+        assert_eq!(bare.key("summary.json"), "summary.json");
+        assert!("/".parse::<S3Target>().is_err());
+    }
+}
@@ -0,0 +1,46 @@
+use std::error::Error;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::cli::WebhookFormat;
+use crate::postprocess::RunResult;
+
+/// Renders the run's scenario scores and deltas vs `baseline` as a monospaced table.
+pub fn format_summary(result: &RunResult) -> String {
+    let mut lines = vec![
+        format!("Lighthouse results for {} ({})", result.url, result.fetch_time),
+        String::from("```"),
+        format!("{:<22} {:>6} {:>8}", "Scenario", "Perf", "ΔPerf"),
+    ];
+    for row in result.ranked_rows() {
+        lines.push(format!("{:<22} {:>6.1} {:>+8.1}", row.name, row.perf_score, row.delta_perf));
+    }
+    lines.push(String::from("```"));
+    lines.join("\n")
+}
+
+/// Wraps `text` in the JSON body each webhook service expects.
+fn payload(format: WebhookFormat, text: String) -> Value {
+    match format {
+        WebhookFormat::Slack => json!({ "text": text }),
+        WebhookFormat::Discord => json!({ "content": text }),
+    }
+}
+
+/// How long posting the summary may take before it is given up on, so an unresponsive
+/// webhook can't hold up the end of the run.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// POSTs the run summary to an incoming webhook, giving up after [`WEBHOOK_TIMEOUT`].
+pub async fn post_summary(url: &str, format: WebhookFormat, result: &RunResult) -> Result<(), Box<dyn Error>> {
+    let response = reqwest::Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()?
+        .post(url)
+        .json(&payload(format, format_summary(result)))
+        .send()
+        .await?;
+    response.error_for_status()?;
+    Ok(())
+}